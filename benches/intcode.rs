@@ -0,0 +1,111 @@
+use aoc2019::intcode::assembler::assemble;
+use aoc2019::intcode::compiled::{day7, sum_loop};
+use aoc2019::intcode::{Computer, Engine, State};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+//Sums 3 * n for n down to 1, a tight arithmetic loop
+const SUM_LOOP: &str = "
+    in [n]
+    loop: mul [n], 3, [t]
+          add [t], [acc], [acc]
+          add [n], -1, [n]
+          jt [n], loop
+          out [acc]
+          hlt
+    n: data 0
+    t: data 0
+    acc: data 0
+";
+
+//Flips the instruction at op between an add and a multiply on every pass
+const SELF_MODIFYING: &str = "
+    in [n]
+    loop: add [op], [delta], [op]
+          mul [delta], -1, [delta]
+    op:   add [value], 1, [value]
+          add [n], -1, [n]
+          jt [n], loop
+          out [value]
+          hlt
+    value: data 0
+    delta: data 1
+    n: data 0
+";
+
+fn run(image: &[i64], input: i64, engine: Engine) -> i64 {
+    let mut computer = Computer::new(image.to_vec(), vec![input]);
+    computer.set_engine(engine);
+    computer.run_to_halt().unwrap();
+    *computer.last_output().unwrap()
+}
+
+fn engines(c: &mut Criterion) {
+    for (name, source) in &[("sum loop", SUM_LOOP), ("self modifying", SELF_MODIFYING)] {
+        let image = assemble(source).unwrap();
+        for engine in &[Engine::Naive, Engine::Cached] {
+            c.bench_function(&format!("{} {:?}", name, engine), |b| {
+                b.iter(|| run(&image, black_box(10_000), *engine))
+            });
+        }
+    }
+}
+
+//Feedback loop of the day7 amplifiers, interpreted and compiled
+fn amplifiers(c: &mut Criterion) {
+    let phases = [9, 8, 7, 6, 5];
+    c.bench_function("day7 feedback interpreted", |b| {
+        b.iter(|| {
+            let mut amplifiers: Vec<_> = phases
+                .iter()
+                .map(|phase| Computer::new(day7::IMAGE.to_vec(), vec![*phase]))
+                .collect();
+            let mut signal = 0;
+            'outer: loop {
+                for amplifier in amplifiers.iter_mut() {
+                    amplifier.add_input(signal);
+                    match amplifier.run().unwrap() {
+                        State::Output(value) => signal = value,
+                        _ => break 'outer,
+                    }
+                }
+            }
+            black_box(signal)
+        })
+    });
+    c.bench_function("day7 feedback compiled", |b| {
+        b.iter(|| {
+            let mut amplifiers: Vec<_> = phases
+                .iter()
+                .map(|phase| day7::machine(vec![*phase]))
+                .collect();
+            let mut signal = 0;
+            'outer: loop {
+                for amplifier in amplifiers.iter_mut() {
+                    amplifier.add_input(signal);
+                    match amplifier.run().unwrap() {
+                        State::Output(value) => signal = value,
+                        _ => break 'outer,
+                    }
+                }
+            }
+            black_box(signal)
+        })
+    });
+}
+
+//The sum loop as translated by the build script against the interpreter
+fn compiled(c: &mut Criterion) {
+    c.bench_function("sum loop interpreted", |b| {
+        b.iter(|| run(&sum_loop::IMAGE, black_box(10_000), Engine::Cached))
+    });
+    c.bench_function("sum loop compiled", |b| {
+        b.iter(|| {
+            let mut machine = sum_loop::machine(vec![black_box(10_000)]);
+            machine.run_to_halt().unwrap();
+            *machine.outputs().last().unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, engines, amplifiers, compiled);
+criterion_main!(benches);
@@ -0,0 +1,43 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+#[allow(dead_code)]
+#[path = "src/intcode/instruction.rs"]
+mod instruction;
+
+#[path = "src/intcode/codegen.rs"]
+mod codegen;
+
+#[allow(dead_code)]
+#[path = "src/intcode/loader.rs"]
+mod loader;
+
+//Programs translated to Rust, each one becomes a module of intcode::compiled
+const PROGRAMS: [&str; 9] = [
+    "day5",
+    "day7",
+    "day7_example1",
+    "day7_example2",
+    "day7_example3",
+    "day7_example4",
+    "day7_example5",
+    "sum_loop",
+    "self_modify",
+];
+
+fn main() {
+    let mut source = String::new();
+    for name in PROGRAMS.iter() {
+        let path = format!("input/{}.txt", name);
+        println!("cargo:rerun-if-changed={}", path);
+        let image: Vec<i64> = loader::load_program(&path)
+            .unwrap_or_else(|error| panic!("Invalid program {}: {}", path, error));
+        source.push_str(&codegen::transpile(&image, name));
+    }
+    println!("cargo:rerun-if-changed=src/intcode/codegen.rs");
+    println!("cargo:rerun-if-changed=src/intcode/instruction.rs");
+    println!("cargo:rerun-if-changed=src/intcode/loader.rs");
+    let out = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out).join("compiled.rs"), source).unwrap();
+}
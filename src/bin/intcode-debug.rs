@@ -0,0 +1,26 @@
+use aoc2019::intcode::debugger::Debugger;
+use aoc2019::intcode::{load_program, Computer};
+use std::env;
+use std::io;
+use std::process;
+
+//Interactive debugger for an Intcode program file
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: intcode-debug <program>");
+            process::exit(2);
+        }
+    };
+    let int_code: Vec<i64> = load_program(&path).unwrap_or_else(|error| {
+        eprintln!("Invalid program {}: {}", path, error);
+        process::exit(1);
+    });
+    let mut debugger = Debugger::new(Computer::new(int_code, vec![]));
+    let stdin = io::stdin();
+    if let Err(error) = debugger.repl(stdin.lock(), io::stdout()) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,165 @@
+use aoc2019::intcode::io::{LineSource, WriterSink};
+use aoc2019::intcode::trace::JsonLinesTracer;
+use aoc2019::intcode::{load_program, Ascii, Computer};
+use std::collections::VecDeque;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+const USAGE: &str = "Usage: intcode [--ascii] [--trace <file>] [--step-limit <steps>] [--dump <file>] <program> [inputs...]";
+
+#[derive(Default)]
+struct Options {
+    program: String,
+    //Values, or lines of text in ASCII mode
+    inputs: Vec<String>,
+    ascii: bool,
+    trace: Option<String>,
+    step_limit: Option<u64>,
+    dump: Option<String>,
+}
+
+fn parse_options<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut program = None;
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}", name))
+        };
+        match arg.as_str() {
+            "--ascii" => options.ascii = true,
+            "--trace" => options.trace = Some(value("--trace")?),
+            "--dump" => options.dump = Some(value("--dump")?),
+            "--step-limit" => {
+                let steps = value("--step-limit")?;
+                options.step_limit = Some(
+                    steps
+                        .parse()
+                        .map_err(|_| format!("Invalid step limit: {}", steps))?,
+                );
+            }
+            _ if program.is_none() && !arg.starts_with("--") => program = Some(arg),
+            _ if program.is_some() => options.inputs.push(arg),
+            _ => return Err(format!("Unknown option {}", arg)),
+        }
+    }
+    options.program = program.ok_or("Missing program")?;
+    if !options.ascii {
+        if let Some(input) = options
+            .inputs
+            .iter()
+            .find(|input| input.parse::<i64>().is_err())
+        {
+            return Err(format!("Invalid input value: {}", input));
+        }
+    }
+    Ok(options)
+}
+
+//Inputs given as arguments replace standard input
+fn run_values(computer: &mut Computer, inputs: &[String]) -> Result<(), String> {
+    let stdout = io::stdout();
+    let mut output = WriterSink::new(stdout.lock());
+    let result = if inputs.is_empty() {
+        let stdin = io::stdin();
+        let mut input = LineSource::new(stdin.lock());
+        let result = computer.run_with(&mut input, &mut output);
+        if let Some(error) = input.error() {
+            return Err(format!("Cannot read input: {}", error));
+        }
+        result
+    } else {
+        for input in inputs {
+            computer.add_input(input.parse().unwrap());
+        }
+        computer.run_with(&mut VecDeque::new(), &mut output)
+    };
+    if let Some(error) = output.error() {
+        return Err(format!("Cannot write output: {}", error));
+    }
+    result.map_err(|error| error.to_string())?;
+    Ok(())
+}
+
+//Lines given as arguments replace the interactive session
+fn play(ascii: &mut Ascii, lines: &[String]) -> Result<(), String> {
+    let stdout = io::stdout();
+    if lines.is_empty() {
+        let stdin = io::stdin();
+        return ascii
+            .session(stdin.lock(), stdout.lock())
+            .map_err(|error| error.to_string());
+    }
+    for line in lines {
+        ascii.send_line(line).map_err(|error| error.to_string())?;
+    }
+    let result = ascii.run();
+    write!(stdout.lock(), "{}", ascii.take_text())
+        .map_err(|error| format!("Cannot write output: {}", error))?;
+    result.map_err(|error| error.to_string())
+}
+
+//Runs the program, returning the error to report if it does not halt
+fn run(options: Options) -> Result<(), String> {
+    let path = &options.program;
+    let program =
+        load_program(path).map_err(|error| format!("Invalid program {}: {}", path, error))?;
+    let mut computer = Computer::new(program, vec![]);
+    if let Some(steps) = options.step_limit {
+        computer.set_step_limit(steps);
+    }
+    if let Some(trace) = &options.trace {
+        let tracer = JsonLinesTracer::create(trace)
+            .map_err(|error| format!("Cannot create {}: {}", trace, error))?;
+        computer.set_tracer(tracer);
+    }
+    let result = if options.ascii {
+        let mut ascii = Ascii::new(computer);
+        let result = play(&mut ascii, &options.inputs);
+        computer = ascii.into_inner();
+        result
+    } else {
+        run_values(&mut computer, &options.inputs)
+    };
+    //The trace is complete whichever way the run ended
+    computer.flush_tracer();
+    let traced = match computer
+        .tracer::<JsonLinesTracer<BufWriter<File>>>()
+        .and_then(JsonLinesTracer::error)
+    {
+        Some(error) => Err(format!("Cannot write trace: {}", error)),
+        None => Ok(()),
+    };
+    if let Some(dump) = &options.dump {
+        computer
+            .snapshot()
+            .save(dump)
+            .map_err(|error| format!("Cannot write {}: {}", dump, error))?;
+    }
+    result?;
+    traced?;
+    if !computer.is_halted() {
+        return Err(format!(
+            "Program is waiting for input at address {}",
+            computer.position()
+        ));
+    }
+    Ok(())
+}
+
+//Runs an Intcode program file, printing every output on its own line.
+//Inputs come from the arguments after the program or, if there are none,
+//from standard input as the program asks for them. In ASCII mode they are
+//lines of text and outputs are printed as text.
+fn main() {
+    let options = parse_options(env::args().skip(1)).unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        process::exit(2);
+    });
+    if let Err(error) = run(options) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
@@ -1,185 +1,221 @@
-mod computer;
-use computer::computer::Computer;
+use crate::intcode::{Computer, IntcodeError, Topology};
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::thread;
 
-//Collect a vector of i32 and generates the corresponding number
-fn permutation_to_number(permutation: Vec<i32>) -> i32 {
-    let mut number = 0;
-    for i in 0..permutation.len() {
-        number += (10 as i32).pow(4 - i as u32) * permutation[i];
-    }
-    number
+//Strongest signal found and every setting sequence giving it, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Best {
+    signal: i64,
+    phases: Vec<Vec<i64>>,
 }
 
-//Returns the maximum thruster signal reached trying every setting sequence.
-fn max_thruster_signal(int_code: Vec<i32>) -> i32 {
-    let mut input: Vec<i32>;
-    let mut output: i32;
-    let mut setting: i32;
-    let mut thruster: Computer;
-    let mut results: HashMap<i32, i32> = HashMap::with_capacity(5);
-    let permutations: Vec<_> = (0..=4).permutations(5).collect();
-    for perm in permutations {
-        output = 0;
-        for thruster_number in 0..=4 {
-            input = vec![perm[thruster_number], output];
-            thruster = Computer::new(int_code.clone(), input);
-            thruster.run();
-            output = thruster.diagnostic_code;
+impl Best {
+    fn keep(best: &mut Option<Best>, signal: i64, phases: Vec<i64>) {
+        match best {
+            Some(best) if signal == best.signal => best.phases.push(phases),
+            Some(best) if signal < best.signal => (),
+            _ => {
+                *best = Some(Best {
+                    signal,
+                    phases: vec![phases],
+                })
+            }
         }
-        setting = permutation_to_number(perm);
-        results.insert(setting, output);
     }
-    let max = results.iter().max_by_key(|(_, v)| *v).unwrap().1;
-    *max
 }
 
-//Returns the maximum thruster signal reached trying every setting sequence.
-fn feedback_loop_mode(int_code: Vec<i32>) -> i32 {
-    let mut input: Vec<i32>;
-    let mut output: i32;
-    let mut setting: i32;
-    let mut thruster;
-    let mut finished;
-    let mut results: HashMap<i32, i32> = HashMap::with_capacity(5);
-    let mut amplifiers: HashMap<i32, Computer>;
-    let permutations: Vec<_> = (5..=9).permutations(5).collect();
-    for perm in permutations {
-        output = 0;
-        amplifiers = HashMap::with_capacity(5);
-        finished = false;
-        //First iteration for initialization
-        for thruster_number in 0..=4 {
-            input = vec![perm[thruster_number], output];
-            thruster = amplifiers
-                .entry(thruster_number as i32)
-                .or_insert_with(|| Computer::new(int_code.clone(), input));
-            thruster.run_until_output();
-            output = thruster.diagnostic_code;
-        }
-        while !finished {
-            for thruster_number in 0..=4 {
-                input = vec![perm[thruster_number], output];
-                thruster = amplifiers
-                    .entry(thruster_number as i32)
-                    .or_insert(Computer::new(int_code.clone(), input));
-                thruster.add_input(output);
-                if !thruster.finished {
-                    thruster.run_until_output();
-                } else {
-                    finished = true;
-                }
-                output = thruster.diagnostic_code;
+//Signal out of the last amplifier, fed with 0 by the first one. In series
+//every amplifier feeds the next one; in a ring the last one feeds the first
+//one as well. None when the last amplifier never sends one.
+fn thruster_signal(
+    amplifier: &Computer,
+    phases: &[i64],
+    ring: bool,
+) -> Result<Option<i64>, IntcodeError> {
+    let mut topology = Topology::new();
+    let thrusters = topology.nodes(amplifier, phases);
+    if ring {
+        topology.ring(&thrusters);
+    } else {
+        topology.series(&thrusters);
+    }
+    if let (Some(first), Some(last)) = (thrusters.first(), thrusters.last()) {
+        topology.send(*first, 0).output(*last);
+    }
+    Ok(topology.run()?.first().cloned().flatten())
+}
+
+//Tries every sequence of distinct phases for the given number of amplifiers,
+//splitting them between threads. Sequences giving no signal are skipped, so
+//it is None if there are not enough phases or none gives a signal.
+fn search_phases(
+    int_code: Vec<i64>,
+    amplifiers: usize,
+    phases: &[i64],
+    ring: bool,
+) -> Result<Option<Best>, IntcodeError> {
+    //Every amplifier is forked from the same freshly loaded machine
+    let amplifier = Computer::new(int_code, vec![]);
+    let settings: Vec<_> = phases.iter().cloned().permutations(amplifiers).collect();
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let chunk = settings.len().div_ceil(threads).max(1);
+    let results: Vec<Result<Option<Best>, IntcodeError>> = thread::scope(|scope| {
+        let searches: Vec<_> = settings
+            .chunks(chunk)
+            .map(|chunk| {
+                let amplifier = amplifier.clone();
+                scope.spawn(move || {
+                    let mut best = None;
+                    for phases in chunk {
+                        if let Some(signal) = thruster_signal(&amplifier, phases, ring)? {
+                            Best::keep(&mut best, signal, phases.clone());
+                        }
+                    }
+                    Ok(best)
+                })
+            })
+            .collect();
+        searches
+            .into_iter()
+            .map(|search| search.join().expect("Searches do not panic"))
+            .collect()
+    });
+    //Chunks are in order, so ties stay in the order they were tried
+    let mut best = None;
+    for result in results {
+        if let Some(found) = result? {
+            for phases in found.phases {
+                Best::keep(&mut best, found.signal, phases);
             }
         }
-        setting = permutation_to_number(perm);
-        results.insert(setting, output);
     }
-    let max = results.iter().max_by_key(|(_, v)| *v).unwrap().1;
-    *max
+    Ok(best)
+}
+
+//Returns the strongest thruster signal with the amplifiers in series.
+fn max_thruster_signal(int_code: Vec<i64>) -> Result<Option<Best>, IntcodeError> {
+    search_phases(int_code, 5, &[0, 1, 2, 3, 4], false)
+}
+
+//Returns the strongest thruster signal with the amplifiers in a ring.
+fn feedback_loop_mode(int_code: Vec<i64>) -> Result<Option<Best>, IntcodeError> {
+    search_phases(int_code, 5, &[5, 6, 7, 8, 9], true)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::intcode::load_program;
+
+    fn example(name: &str) -> Vec<i64> {
+        load_program(format!("input/{}.txt", name)).unwrap()
+    }
+
+    fn winner(signal: i64, phases: &[i64]) -> Option<Best> {
+        Some(Best {
+            signal,
+            phases: vec![phases.to_vec()],
+        })
+    }
+
+    #[test]
+    fn ties_are_all_reported() {
+        //Ignores its phase and adds one to the signal
+        let int_code = vec![3, 11, 3, 12, 1001, 12, 1, 12, 4, 12, 99, 0, 0];
+        let best = search_phases(int_code.clone(), 3, &[0, 1, 2, 3], false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(best.signal, 3);
+        assert_eq!(best.phases.len(), 24);
+        assert_eq!(best.phases[0], vec![0, 1, 2]);
+        assert_eq!(best.phases[23], vec![3, 2, 1]);
+        assert_eq!(search_phases(int_code, 3, &[0, 1], false), Ok(None));
+    }
+
+    #[test]
+    fn missing_signals_are_skipped() {
+        //Passes the signal on unless its phase is 0
+        let int_code = vec![3, 10, 3, 11, 1006, 10, 9, 4, 11, 99, 0, 0];
+        assert_eq!(
+            search_phases(int_code.clone(), 1, &[0, 1, 2], false),
+            Ok(Some(Best {
+                signal: 0,
+                phases: vec![vec![1], vec![2]]
+            }))
+        );
+        assert_eq!(search_phases(int_code, 1, &[0], false), Ok(None));
+    }
+
+    #[test]
+    fn any_number_of_amplifiers() {
+        //Example 1 of part 1 outputs ten times the signal plus the phase
+        let int_code = example("day7_example1");
+        assert_eq!(
+            search_phases(int_code, 3, &[2, 5, 7, 1], false),
+            Ok(winner(752, &[7, 5, 2]))
+        );
+    }
 
     #[test]
     fn example1_part1() {
-        let int_code = vec![
-            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
-        ];
-        assert_eq!(dbg!(max_thruster_signal(int_code)), 43210);
+        let int_code = example("day7_example1");
+        assert_eq!(
+            max_thruster_signal(int_code),
+            Ok(winner(43210, &[4, 3, 2, 1, 0]))
+        );
     }
 
     #[test]
     fn example2_part1() {
-        let int_code = vec![
-            3, 23, 3, 24, 1002, 24, 10, 24, 1002, 23, -1, 23, 101, 5, 23, 23, 1, 24, 23, 23, 4, 23,
-            99, 0, 0,
-        ];
-        assert_eq!(dbg!(max_thruster_signal(int_code)), 54321);
+        let int_code = example("day7_example2");
+        assert_eq!(
+            max_thruster_signal(int_code),
+            Ok(winner(54321, &[0, 1, 2, 3, 4]))
+        );
     }
 
     #[test]
     fn example3_part1() {
-        let int_code = vec![
-            3, 31, 3, 32, 1002, 32, 10, 32, 1001, 31, -2, 31, 1007, 31, 0, 33, 1002, 33, 7, 33, 1,
-            33, 31, 31, 1, 32, 31, 31, 4, 31, 99, 0, 0, 0,
-        ];
-        assert_eq!(dbg!(max_thruster_signal(int_code)), 65210);
+        let int_code = example("day7_example3");
+        assert_eq!(
+            max_thruster_signal(int_code),
+            Ok(winner(65210, &[1, 0, 4, 3, 2]))
+        );
     }
 
     #[test]
     fn test_part1() {
-        let int_code = vec![
-            3, 8, 1001, 8, 10, 8, 105, 1, 0, 0, 21, 30, 47, 64, 81, 98, 179, 260, 341, 422, 99999,
-            3, 9, 1001, 9, 5, 9, 4, 9, 99, 3, 9, 1002, 9, 5, 9, 101, 4, 9, 9, 102, 2, 9, 9, 4, 9,
-            99, 3, 9, 102, 3, 9, 9, 101, 2, 9, 9, 1002, 9, 3, 9, 4, 9, 99, 3, 9, 1001, 9, 5, 9,
-            1002, 9, 3, 9, 1001, 9, 3, 9, 4, 9, 99, 3, 9, 1002, 9, 3, 9, 101, 2, 9, 9, 102, 5, 9,
-            9, 4, 9, 99, 3, 9, 1001, 9, 2, 9, 4, 9, 3, 9, 1001, 9, 2, 9, 4, 9, 3, 9, 1001, 9, 2, 9,
-            4, 9, 3, 9, 1001, 9, 2, 9, 4, 9, 3, 9, 101, 2, 9, 9, 4, 9, 3, 9, 1001, 9, 2, 9, 4, 9,
-            3, 9, 1001, 9, 1, 9, 4, 9, 3, 9, 1001, 9, 2, 9, 4, 9, 3, 9, 101, 2, 9, 9, 4, 9, 3, 9,
-            1001, 9, 2, 9, 4, 9, 99, 3, 9, 101, 2, 9, 9, 4, 9, 3, 9, 102, 2, 9, 9, 4, 9, 3, 9,
-            1001, 9, 1, 9, 4, 9, 3, 9, 1001, 9, 2, 9, 4, 9, 3, 9, 102, 2, 9, 9, 4, 9, 3, 9, 1002,
-            9, 2, 9, 4, 9, 3, 9, 102, 2, 9, 9, 4, 9, 3, 9, 102, 2, 9, 9, 4, 9, 3, 9, 102, 2, 9, 9,
-            4, 9, 3, 9, 1002, 9, 2, 9, 4, 9, 99, 3, 9, 101, 1, 9, 9, 4, 9, 3, 9, 101, 2, 9, 9, 4,
-            9, 3, 9, 101, 2, 9, 9, 4, 9, 3, 9, 101, 1, 9, 9, 4, 9, 3, 9, 101, 2, 9, 9, 4, 9, 3, 9,
-            1002, 9, 2, 9, 4, 9, 3, 9, 101, 2, 9, 9, 4, 9, 3, 9, 1001, 9, 1, 9, 4, 9, 3, 9, 102, 2,
-            9, 9, 4, 9, 3, 9, 1002, 9, 2, 9, 4, 9, 99, 3, 9, 1001, 9, 2, 9, 4, 9, 3, 9, 1001, 9, 1,
-            9, 4, 9, 3, 9, 102, 2, 9, 9, 4, 9, 3, 9, 101, 1, 9, 9, 4, 9, 3, 9, 1002, 9, 2, 9, 4, 9,
-            3, 9, 101, 1, 9, 9, 4, 9, 3, 9, 102, 2, 9, 9, 4, 9, 3, 9, 1001, 9, 2, 9, 4, 9, 3, 9,
-            1002, 9, 2, 9, 4, 9, 3, 9, 101, 2, 9, 9, 4, 9, 99, 3, 9, 1001, 9, 2, 9, 4, 9, 3, 9,
-            101, 2, 9, 9, 4, 9, 3, 9, 1001, 9, 1, 9, 4, 9, 3, 9, 1002, 9, 2, 9, 4, 9, 3, 9, 101, 1,
-            9, 9, 4, 9, 3, 9, 1001, 9, 1, 9, 4, 9, 3, 9, 1002, 9, 2, 9, 4, 9, 3, 9, 101, 1, 9, 9,
-            4, 9, 3, 9, 1002, 9, 2, 9, 4, 9, 3, 9, 102, 2, 9, 9, 4, 9, 99,
-        ];
-        assert_eq!(dbg!(max_thruster_signal(int_code)), 45730);
+        let int_code: Vec<i64> = load_program("input/day7.txt").unwrap();
+        assert_eq!(
+            max_thruster_signal(int_code),
+            Ok(winner(45730, &[0, 3, 1, 2, 4]))
+        );
     }
 
     #[test]
     fn example1_part2() {
-        let int_code = vec![
-            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
-            28, 1005, 28, 6, 99, 0, 0, 5,
-        ];
-        assert_eq!(feedback_loop_mode(int_code), 139629729);
+        let int_code = example("day7_example4");
+        assert_eq!(
+            feedback_loop_mode(int_code),
+            Ok(winner(139629729, &[9, 8, 7, 6, 5]))
+        );
     }
 
     #[test]
     fn example2_part2() {
-        let int_code = vec![
-            3, 52, 1001, 52, -5, 52, 3, 53, 1, 52, 56, 54, 1007, 54, 5, 55, 1005, 55, 26, 1001, 54,
-            -5, 54, 1105, 1, 12, 1, 53, 54, 53, 1008, 54, 0, 55, 1001, 55, 1, 55, 2, 53, 55, 53, 4,
-            53, 1001, 56, -1, 56, 1005, 56, 6, 99, 0, 0, 0, 0, 10,
-        ];
-        assert_eq!(feedback_loop_mode(int_code), 18216);
+        let int_code = example("day7_example5");
+        assert_eq!(
+            feedback_loop_mode(int_code),
+            Ok(winner(18216, &[9, 7, 8, 5, 6]))
+        );
     }
 
     #[test]
     fn test_part2() {
-        let int_code = vec![
-            3, 8, 1001, 8, 10, 8, 105, 1, 0, 0, 21, 30, 47, 64, 81, 98, 179, 260, 341, 422, 99999,
-            3, 9, 1001, 9, 5, 9, 4, 9, 99, 3, 9, 1002, 9, 5, 9, 101, 4, 9, 9, 102, 2, 9, 9, 4, 9,
-            99, 3, 9, 102, 3, 9, 9, 101, 2, 9, 9, 1002, 9, 3, 9, 4, 9, 99, 3, 9, 1001, 9, 5, 9,
-            1002, 9, 3, 9, 1001, 9, 3, 9, 4, 9, 99, 3, 9, 1002, 9, 3, 9, 101, 2, 9, 9, 102, 5, 9,
-            9, 4, 9, 99, 3, 9, 1001, 9, 2, 9, 4, 9, 3, 9, 1001, 9, 2, 9, 4, 9, 3, 9, 1001, 9, 2, 9,
-            4, 9, 3, 9, 1001, 9, 2, 9, 4, 9, 3, 9, 101, 2, 9, 9, 4, 9, 3, 9, 1001, 9, 2, 9, 4, 9,
-            3, 9, 1001, 9, 1, 9, 4, 9, 3, 9, 1001, 9, 2, 9, 4, 9, 3, 9, 101, 2, 9, 9, 4, 9, 3, 9,
-            1001, 9, 2, 9, 4, 9, 99, 3, 9, 101, 2, 9, 9, 4, 9, 3, 9, 102, 2, 9, 9, 4, 9, 3, 9,
-            1001, 9, 1, 9, 4, 9, 3, 9, 1001, 9, 2, 9, 4, 9, 3, 9, 102, 2, 9, 9, 4, 9, 3, 9, 1002,
-            9, 2, 9, 4, 9, 3, 9, 102, 2, 9, 9, 4, 9, 3, 9, 102, 2, 9, 9, 4, 9, 3, 9, 102, 2, 9, 9,
-            4, 9, 3, 9, 1002, 9, 2, 9, 4, 9, 99, 3, 9, 101, 1, 9, 9, 4, 9, 3, 9, 101, 2, 9, 9, 4,
-            9, 3, 9, 101, 2, 9, 9, 4, 9, 3, 9, 101, 1, 9, 9, 4, 9, 3, 9, 101, 2, 9, 9, 4, 9, 3, 9,
-            1002, 9, 2, 9, 4, 9, 3, 9, 101, 2, 9, 9, 4, 9, 3, 9, 1001, 9, 1, 9, 4, 9, 3, 9, 102, 2,
-            9, 9, 4, 9, 3, 9, 1002, 9, 2, 9, 4, 9, 99, 3, 9, 1001, 9, 2, 9, 4, 9, 3, 9, 1001, 9, 1,
-            9, 4, 9, 3, 9, 102, 2, 9, 9, 4, 9, 3, 9, 101, 1, 9, 9, 4, 9, 3, 9, 1002, 9, 2, 9, 4, 9,
-            3, 9, 101, 1, 9, 9, 4, 9, 3, 9, 102, 2, 9, 9, 4, 9, 3, 9, 1001, 9, 2, 9, 4, 9, 3, 9,
-            1002, 9, 2, 9, 4, 9, 3, 9, 101, 2, 9, 9, 4, 9, 99, 3, 9, 1001, 9, 2, 9, 4, 9, 3, 9,
-            101, 2, 9, 9, 4, 9, 3, 9, 1001, 9, 1, 9, 4, 9, 3, 9, 1002, 9, 2, 9, 4, 9, 3, 9, 101, 1,
-            9, 9, 4, 9, 3, 9, 1001, 9, 1, 9, 4, 9, 3, 9, 1002, 9, 2, 9, 4, 9, 3, 9, 101, 1, 9, 9,
-            4, 9, 3, 9, 1002, 9, 2, 9, 4, 9, 3, 9, 102, 2, 9, 9, 4, 9, 99,
-        ];
-        assert_eq!(feedback_loop_mode(int_code), 5406484);
+        let int_code: Vec<i64> = load_program("input/day7.txt").unwrap();
+        assert_eq!(
+            feedback_loop_mode(int_code),
+            Ok(winner(5406484, &[5, 7, 9, 8, 6]))
+        );
     }
 }
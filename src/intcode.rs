@@ -0,0 +1,40 @@
+pub mod analysis;
+pub mod ascii;
+pub mod assembler;
+mod codegen;
+pub mod compiled;
+mod computer;
+pub mod debugger;
+pub mod disassembler;
+mod engine;
+mod error;
+mod instruction;
+pub mod io;
+pub mod loader;
+mod memory;
+pub mod network;
+pub mod scheduler;
+pub mod search;
+pub mod snapshot;
+pub mod symbolic;
+pub mod topology;
+pub mod trace;
+mod word;
+
+pub use ascii::{Ascii, SessionError};
+pub use codegen::transpile;
+pub use computer::{Computer, State};
+pub use engine::Engine;
+pub use error::IntcodeError;
+pub use instruction::{DecodeError, Instruction, Operation, ParameterMode};
+pub use io::{InputSource, OutputSink};
+pub use loader::{load_program, parse_program, LoadError};
+pub use memory::{MemoryError, DEFAULT_MEMORY_LIMIT};
+pub use network::{Merge, Network};
+pub use scheduler::Scheduler;
+pub use search::{Search, Solution, Variable};
+pub use snapshot::{Snapshot, SnapshotError};
+pub use symbolic::{Expr, Method, Symbolic, Target};
+pub use topology::Topology;
+pub use trace::{Profiler, RingTracer, TraceEvent, Tracer};
+pub use word::Word;
@@ -0,0 +1,627 @@
+use super::disassembler::{decode_in, Decoded};
+use super::instruction::{Operation, ParameterMode};
+use super::word::Word;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+//Static analysis of a memory image: the control-flow graph of the code
+//reachable from address 0, jumps whose target is only known at run time,
+//writes into code and instructions nothing jumps to.
+//
+//Writes in relative mode depend on the relative base and are not followed,
+//and code reached through indirect jumps is reported as unreachable. When
+//the control flow reaches an opcode the reachable code writes, the
+//analysis goes on with every operation it could become, keeping the
+//parameter modes found in the image.
+
+//Opcodes a self-modified instruction may be given
+const OPCODES: [i64; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    //Execution continues with the next instruction
+    Next,
+    //A jump is taken
+    Jump,
+}
+
+//Edge between the blocks starting at two addresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+//Straight-line code only entered at its first instruction
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block<W> {
+    pub start: usize,
+    pub end: usize,
+    pub instructions: Vec<Decoded<W>>,
+}
+
+//Instruction writing into a word of some other instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeWrite {
+    pub writer: usize,
+    pub target: usize,
+    //Address of the instruction the target belongs to, or of the invalid
+    //opcode being written
+    pub instruction: usize,
+}
+
+impl CodeWrite {
+    pub fn writes_opcode(&self) -> bool {
+        self.target == self.instruction
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Analysis<W> {
+    pub blocks: Vec<Block<W>>,
+    pub edges: Vec<Edge>,
+    //Jumps whose target is read from memory
+    pub indirect_jumps: Vec<usize>,
+    pub code_writes: Vec<CodeWrite>,
+    //Words left over that decode as instructions
+    pub unreachable: Vec<Decoded<W>>,
+    //Addresses the control flow reaches without a valid instruction there
+    pub invalid: Vec<usize>,
+    //Addresses the control flow reaches whose opcode the program writes
+    //before running it. Their edges lead to what every candidate operation
+    //would run next.
+    pub self_modified: Vec<usize>,
+}
+
+impl<W> Analysis<W> {
+    pub fn block(&self, start: usize) -> Option<&Block<W>> {
+        self.blocks.iter().find(|block| block.start == start)
+    }
+
+    //Start of the block holding the instruction at an address
+    pub fn block_of(&self, address: usize) -> Option<usize> {
+        self.blocks
+            .iter()
+            .find(|block| block.start <= address && address < block.end)
+            .map(|block| block.start)
+    }
+
+    pub fn successors(&self, start: usize) -> Vec<usize> {
+        self.edges
+            .iter()
+            .filter(|edge| edge.from == start)
+            .map(|edge| edge.to)
+            .collect()
+    }
+}
+
+//Whether a conditional jump is always or never taken, None when it depends
+//on memory
+fn always_jumps<W: Word>(decoded: &Decoded<W>) -> Option<bool> {
+    let condition = &decoded.operands[0];
+    if condition.mode != ParameterMode::Immediate {
+        return None;
+    }
+    let zero = condition.value.is_zero();
+    match decoded.instruction.operation {
+        Operation::JumpIfTrue => Some(!zero),
+        _ => Some(zero),
+    }
+}
+
+fn is_jump<W: Word>(decoded: &Decoded<W>) -> bool {
+    matches!(
+        decoded.instruction.operation,
+        Operation::JumpIfTrue | Operation::JumpIfFalse
+    )
+}
+
+fn is_indirect<W: Word>(decoded: &Decoded<W>) -> bool {
+    is_jump(decoded)
+        && decoded.operands[1].mode != ParameterMode::Immediate
+        && always_jumps(decoded) != Some(false)
+}
+
+//Addresses execution can continue at after an instruction
+fn successors<W: Word>(decoded: &Decoded<W>) -> Vec<(usize, EdgeKind)> {
+    if decoded.instruction.operation == Operation::Halt {
+        return Vec::new();
+    }
+    if !is_jump(decoded) {
+        return vec![(decoded.next(), EdgeKind::Next)];
+    }
+    let jumps = always_jumps(decoded);
+    let mut successors = Vec::new();
+    if jumps != Some(false) {
+        if let Some(target) = decoded.jump_target() {
+            successors.push((target, EdgeKind::Jump));
+        }
+    }
+    if jumps != Some(true) {
+        successors.push((decoded.next(), EdgeKind::Next));
+    }
+    successors
+}
+
+//Cell written by an instruction when it does not depend on the relative base
+fn written_address<W: Word>(decoded: &Decoded<W>) -> Option<usize> {
+    let offset = decoded.instruction.operation.written_parameter()?;
+    let operand = &decoded.operands[offset - 1];
+    match operand.mode {
+        ParameterMode::Position => match operand.value.to_i64() {
+            Some(address) if address >= 0 => Some(address as usize),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+//Instructions the word at an address could become once the program writes
+//its opcode, with the modes left as they are
+fn candidates<W: Word>(image: &[W], address: usize) -> Vec<Decoded<W>> {
+    let modes = match image[address].to_i64() {
+        Some(value) => value / 100,
+        None => return Vec::new(),
+    };
+    let end = image.len().min(address + 4);
+    OPCODES
+        .iter()
+        .filter_map(|opcode| {
+            let mut window = image[address..end].to_vec();
+            window[0] = W::from_i64(modes.checked_mul(100)?.checked_add(*opcode)?)?;
+            let mut decoded = decode_in(&window, 0)?;
+            decoded.address = address;
+            Some(decoded)
+        })
+        .collect()
+}
+
+pub fn analyse<W: Word>(image: &[W]) -> Analysis<W> {
+    //Follow the control flow from address 0
+    let mut code: BTreeMap<usize, Decoded<W>> = BTreeMap::new();
+    let mut covered = vec![false; image.len()];
+    let mut leaders = BTreeSet::new();
+    leaders.insert(0);
+    let mut invalid = BTreeSet::new();
+    //Self-modified addresses and where their candidates continue
+    let mut modified: BTreeMap<usize, Vec<(usize, EdgeKind)>> = BTreeMap::new();
+    let mut pending = vec![0];
+    loop {
+        while let Some(address) = pending.pop() {
+            if address >= image.len()
+                || code.contains_key(&address)
+                || modified.contains_key(&address)
+            {
+                continue;
+            }
+            let decoded = match decode_in(image, address) {
+                Some(decoded) => decoded,
+                None => {
+                    invalid.insert(address);
+                    continue;
+                }
+            };
+            //Instructions overlapping already decoded code are left as data
+            if covered[address..decoded.next()].iter().any(|word| *word) {
+                continue;
+            }
+            covered[address..decoded.next()]
+                .iter_mut()
+                .for_each(|word| *word = true);
+            for (successor, kind) in successors(&decoded) {
+                if kind == EdgeKind::Jump || is_jump(&decoded) {
+                    leaders.insert(successor);
+                }
+                pending.push(successor);
+            }
+            if is_jump(&decoded) || decoded.instruction.operation == Operation::Halt {
+                leaders.insert(decoded.next());
+            }
+            code.insert(address, decoded);
+        }
+        //Invalid opcodes the code writes are explored as every candidate,
+        //which may reach more code writing more of them
+        let written: BTreeSet<usize> = code.values().filter_map(written_address).collect();
+        let rewritten: Vec<usize> = invalid.intersection(&written).cloned().collect();
+        if rewritten.is_empty() {
+            break;
+        }
+        for address in rewritten {
+            invalid.remove(&address);
+            covered[address] = true;
+            let mut next = Vec::new();
+            for candidate in candidates(image, address) {
+                for (successor, kind) in successors(&candidate) {
+                    //Candidates leading to invalid code are ignored rather
+                    //than reported
+                    if successor < image.len()
+                        && decode_in(image, successor).is_some()
+                        && !next.contains(&(successor, kind))
+                    {
+                        leaders.insert(successor);
+                        pending.push(successor);
+                        next.push((successor, kind));
+                    }
+                }
+            }
+            modified.insert(address, next);
+        }
+    }
+
+    let mut blocks = Vec::new();
+    let mut edges = Vec::new();
+    for start in leaders.iter().filter(|start| code.contains_key(start)) {
+        let mut instructions: Vec<Decoded<W>> = Vec::new();
+        let mut address = *start;
+        while let Some(decoded) = code.get(&address) {
+            if address != *start && leaders.contains(&address) {
+                break;
+            }
+            instructions.push(decoded.clone());
+            address = decoded.next();
+            if is_jump(decoded) {
+                break;
+            }
+        }
+        let last = instructions
+            .last()
+            .expect("Blocks start with an instruction");
+        for (to, kind) in successors(last) {
+            if code.contains_key(&to) || invalid.contains(&to) || modified.contains_key(&to) {
+                edges.push(Edge {
+                    from: *start,
+                    to,
+                    kind,
+                });
+            }
+        }
+        blocks.push(Block {
+            start: *start,
+            end: address,
+            instructions,
+        });
+    }
+    for (from, next) in &modified {
+        for (to, kind) in next {
+            if code.contains_key(to) {
+                edges.push(Edge {
+                    from: *from,
+                    to: *to,
+                    kind: *kind,
+                });
+            }
+        }
+    }
+
+    //Sweep the gaps left between reachable code
+    let mut unreachable = Vec::new();
+    let mut address = 0;
+    while address < image.len() {
+        if !covered[address] {
+            let decoded = decode_in(image, address)
+                .filter(|decoded| !covered[address..decoded.next()].iter().any(|word| *word));
+            if let Some(decoded) = decoded {
+                address = decoded.next();
+                unreachable.push(decoded);
+                continue;
+            }
+        }
+        address += 1;
+    }
+
+    //Unreachable code may still run through indirect jumps, so it is checked
+    //too
+    let all: BTreeMap<usize, &Decoded<W>> = code
+        .values()
+        .chain(unreachable.iter())
+        .map(|decoded| (decoded.address, decoded))
+        .collect();
+    let indirect_jumps = all
+        .values()
+        .filter(|decoded| is_indirect(decoded))
+        .map(|decoded| decoded.address)
+        .collect();
+    let code_writes = all
+        .values()
+        .filter_map(|decoded| {
+            let target = written_address(decoded)?;
+            let instruction = if invalid.contains(&target) || modified.contains_key(&target) {
+                target
+            } else {
+                let (instruction, written) = all.range(..=target).next_back()?;
+                if target >= written.next() {
+                    return None;
+                }
+                *instruction
+            };
+            Some(CodeWrite {
+                writer: decoded.address,
+                target,
+                instruction,
+            })
+        })
+        .collect();
+
+    Analysis {
+        blocks,
+        edges,
+        indirect_jumps,
+        code_writes,
+        unreachable,
+        invalid: invalid.into_iter().collect(),
+        self_modified: modified.into_keys().collect(),
+    }
+}
+
+impl<W: Word> fmt::Display for Analysis<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for block in &self.blocks {
+            write!(f, "block {}..{}", block.start, block.end)?;
+            let successors: Vec<_> = self
+                .successors(block.start)
+                .iter()
+                .map(|to| to.to_string())
+                .collect();
+            if !successors.is_empty() {
+                write!(f, " -> {}", successors.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+        for address in &self.self_modified {
+            write!(f, "self-modified code at {}", address)?;
+            let successors: Vec<_> = self
+                .successors(*address)
+                .iter()
+                .map(|to| to.to_string())
+                .collect();
+            if !successors.is_empty() {
+                write!(f, " -> {}", successors.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+        for address in &self.invalid {
+            writeln!(f, "invalid code at {}", address)?;
+        }
+        for address in &self.indirect_jumps {
+            writeln!(f, "indirect jump at {}", address)?;
+        }
+        for write in &self.code_writes {
+            if write.writes_opcode() {
+                writeln!(
+                    f,
+                    "write at {} into the opcode of {}",
+                    write.writer, write.instruction
+                )?;
+            } else {
+                writeln!(
+                    f,
+                    "write at {} into parameter {} of {}",
+                    write.writer,
+                    write.target - write.instruction,
+                    write.instruction
+                )?;
+            }
+        }
+        for decoded in &self.unreachable {
+            writeln!(f, "unreachable {}: {}", decoded.address, decoded)?;
+        }
+        Ok(())
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn label<W: Word>(instructions: &[Decoded<W>]) -> String {
+    instructions
+        .iter()
+        .map(|decoded| format!("{}: {}\\l", decoded.address, escape(&decoded.to_string())))
+        .collect()
+}
+
+impl<W: Word> Analysis<W> {
+    //Graphviz form of the graph. Blocks and self-modified opcodes written
+    //into are drawn in red and indirect jumps lead to a node of their own.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph intcode {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        let written: BTreeSet<_> = self
+            .code_writes
+            .iter()
+            .filter_map(|write| self.block_of(write.instruction))
+            .collect();
+        for block in &self.blocks {
+            let color = if written.contains(&block.start) {
+                ", color=red"
+            } else {
+                ""
+            };
+            dot.push_str(&format!(
+                "    b{} [label=\"{}\"{}];\n",
+                block.start,
+                label(&block.instructions),
+                color
+            ));
+        }
+        for address in &self.self_modified {
+            dot.push_str(&format!(
+                "    b{0} [label=\"{0}: self-modified\", color=red];\n",
+                address
+            ));
+        }
+        for address in &self.invalid {
+            dot.push_str(&format!("    b{0} [label=\"{0}: invalid\"];\n", address));
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::Next => "",
+                EdgeKind::Jump => " [label=\"jump\"]",
+            };
+            dot.push_str(&format!("    b{} -> b{}{};\n", edge.from, edge.to, style));
+        }
+        if !self.indirect_jumps.is_empty() {
+            dot.push_str("    indirect [shape=ellipse, label=\"?\"];\n");
+            for address in &self.indirect_jumps {
+                if let Some(start) = self.block_of(*address) {
+                    dot.push_str(&format!("    b{} -> indirect [style=dashed];\n", start));
+                }
+            }
+        }
+        if !self.unreachable.is_empty() {
+            dot.push_str(&format!(
+                "    unreachable [label=\"{}\", style=dashed];\n",
+                label(&self.unreachable)
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::loader::load_program;
+
+    #[test]
+    fn loop_graph() {
+        //Counts down from 3, printing every value
+        let image = vec![4, 12, 1001, 12, -1, 12, 1005, 12, 0, 99, 7, 8, 3];
+        let analysis = analyse(&image);
+        let starts: Vec<_> = analysis.blocks.iter().map(|block| block.start).collect();
+        assert_eq!(starts, vec![0, 9]);
+        assert_eq!(analysis.successors(0), vec![0, 9]);
+        assert!(analysis.successors(9).is_empty());
+        assert!(analysis.indirect_jumps.is_empty());
+        assert!(analysis.code_writes.is_empty());
+        assert!(analysis.unreachable.is_empty());
+        assert_eq!(
+            analysis.to_string(),
+            "block 0..9 -> 0, 9\n\
+             block 9..10\n"
+        );
+    }
+
+    #[test]
+    fn constant_conditions_have_one_edge() {
+        //Always jumps over the output
+        let image = vec![1105, 1, 5, 104, 1, 99];
+        let analysis = analyse(&image);
+        assert_eq!(analysis.successors(0), vec![5]);
+        assert_eq!(analysis.block(5).unwrap().end, 6);
+        assert_eq!(analysis.unreachable.len(), 1);
+        assert_eq!(analysis.unreachable[0].address, 3);
+    }
+
+    #[test]
+    fn indirect_jumps_and_unreachable_code() {
+        //The jump target comes from memory, so address 5 is only found by sweeping
+        let image = vec![106, 0, 9, 99, 0, 104, 1, 99, 0, 5];
+        let analysis = analyse(&image);
+        assert_eq!(analysis.indirect_jumps, vec![0]);
+        //The jump is always taken
+        assert!(analysis.successors(0).is_empty());
+        assert_eq!(
+            analysis.to_string(),
+            "block 0..3\n\
+             indirect jump at 0\n\
+             unreachable 3: hlt\n\
+             unreachable 5: out 1\n\
+             unreachable 7: hlt\n"
+        );
+    }
+
+    #[test]
+    fn writes_into_code() {
+        let image = vec![1, 9, 12, 9, 3, 10, 1106, 0, 9, 4, 12, 99, 1];
+        let analysis = analyse(&image);
+        let writes: Vec<_> = analysis
+            .code_writes
+            .iter()
+            .map(|write| (write.writer, write.instruction, write.writes_opcode()))
+            .collect();
+        assert_eq!(writes, vec![(0, 9, true), (4, 9, false)]);
+        assert_eq!(
+            analysis.to_string(),
+            "block 0..9 -> 9\n\
+             block 9..12\n\
+             write at 0 into the opcode of 9\n\
+             write at 4 into parameter 1 of 9\n"
+        );
+        assert!(analysis
+            .to_dot()
+            .contains("    b9 [label=\"9: out [12]\\l11: hlt\\l\", color=red];\n"));
+    }
+
+    #[test]
+    fn invalid_and_self_modified_opcodes() {
+        //Jumps to 42, which nothing writes
+        let analysis = analyse(&[1105, 1, 3, 42]);
+        assert_eq!(analysis.invalid, vec![3]);
+        assert!(analysis.self_modified.is_empty());
+        assert_eq!(analysis.successors(0), vec![3]);
+        //Turns the 0 at 4 into a 99 before reaching it
+        let analysis = analyse(&[1101, 99, 0, 4, 0]);
+        assert!(analysis.invalid.is_empty());
+        assert_eq!(analysis.self_modified, vec![4]);
+        assert_eq!(analysis.successors(0), vec![4]);
+    }
+
+    #[test]
+    fn day5_diagnostic() {
+        let image: Vec<i64> = load_program("input/day5.txt").unwrap();
+        let analysis = analyse(&image);
+        //The opcode at 6 gets the input added to it: 1 makes it an add going
+        //on with the part 1 checks and 5 a jump to the part 2 ones
+        assert_eq!(analysis.self_modified, vec![6]);
+        assert!(analysis.invalid.is_empty());
+        assert_eq!(analysis.successors(0), vec![6]);
+        assert_eq!(analysis.successors(6), vec![10, 238]);
+        let starts: Vec<_> = analysis.blocks.iter().map(|block| block.start).collect();
+        assert_eq!(
+            starts,
+            vec![0, 10, 238, 241, 247, 250, 253, 256, 259, 265, 268, 271, 274, 280]
+        );
+        //Part 1 runs straight through to its halt
+        let part1 = analysis.block(10).unwrap();
+        assert_eq!(part1.end, 223);
+        assert!(analysis.successors(10).is_empty());
+        //Part 2 checks the jumps, failing at 99999 when one goes wrong
+        assert_eq!(analysis.successors(238), vec![241]);
+        assert_eq!(analysis.successors(250), vec![256, 253]);
+        assert_eq!(analysis.successors(268), vec![274, 271]);
+        assert_eq!(analysis.successors(274), vec![280]);
+        //The test harness stores return addresses over address 0 and jumps
+        //through them
+        assert_eq!(analysis.indirect_jumps, vec![288, 308]);
+        let writes: Vec<_> = analysis
+            .code_writes
+            .iter()
+            .map(|write| (write.writer, write.target))
+            .collect();
+        assert_eq!(writes, vec![(2, 6), (284, 0), (304, 0)]);
+        assert!(analysis.to_string().starts_with(
+            "block 0..6 -> 6\n\
+             block 10..223\n\
+             block 238..241 -> 241\n"
+        ));
+        assert!(analysis
+            .to_string()
+            .contains("block 280..291\nself-modified code at 6 -> 10, 238\n"));
+        let dot = analysis.to_dot();
+        assert!(dot.starts_with(
+            "digraph intcode {\n    \
+             node [shape=box, fontname=\"monospace\"];\n    \
+             b0 [label=\"0: in [225]\\l2: add [225], [6], [6]\\l\", color=red];\n    \
+             b10 [label=\"10: out 0\\l"
+        ));
+        assert!(dot.contains(
+            "    b6 [label=\"6: self-modified\", color=red];\n    \
+             b0 -> b6;\n"
+        ));
+        assert!(dot.contains("    b6 -> b10;\n    b6 -> b238 [label=\"jump\"];\n"));
+        assert!(dot.ends_with("676: hlt\\l\", style=dashed];\n}\n"));
+    }
+}
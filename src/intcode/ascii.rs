@@ -0,0 +1,214 @@
+use super::computer::{Computer, State};
+use super::error::IntcodeError;
+use super::word::Word;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+//Text front end for programs that talk in ASCII. Lines typed for the program
+//become one input code per character followed by a newline, and its output
+//codes are rendered back as characters. Values outside the ASCII range are
+//usually answers, so they are rendered as numbers on their own line.
+
+#[derive(Debug)]
+pub enum SessionError {
+    Io(io::Error),
+    Intcode(IntcodeError),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::Io(error) => write!(f, "Session input or output failed: {}", error),
+            SessionError::Intcode(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+impl From<io::Error> for SessionError {
+    fn from(error: io::Error) -> Self {
+        SessionError::Io(error)
+    }
+}
+
+impl From<IntcodeError> for SessionError {
+    fn from(error: IntcodeError) -> Self {
+        SessionError::Intcode(error)
+    }
+}
+
+//Input codes for a line of text, newline included
+pub fn encode<W: Word>(line: &str) -> Result<Vec<W>, IntcodeError> {
+    line.chars()
+        .chain(Some('\n'))
+        .map(|c| {
+            let value = i64::from(u32::from(c));
+            W::from_i64(value).ok_or(IntcodeError::InputOutOfRange { value })
+        })
+        .collect()
+}
+
+pub fn render<W: Word>(values: &[W]) -> String {
+    let mut text = String::new();
+    for value in values {
+        match value.to_i64() {
+            Some(code @ 0..=127) => text.push(code as u8 as char),
+            _ => {
+                if !text.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                text.push_str(&format!("{}\n", value));
+            }
+        }
+    }
+    text
+}
+
+pub struct Ascii<W: Word = i64> {
+    computer: Computer<W>,
+}
+
+impl<W: Word> Ascii<W> {
+    pub fn new(computer: Computer<W>) -> Self {
+        Ascii { computer }
+    }
+
+    pub fn computer(&self) -> &Computer<W> {
+        &self.computer
+    }
+
+    pub fn computer_mut(&mut self) -> &mut Computer<W> {
+        &mut self.computer
+    }
+
+    pub fn into_inner(self) -> Computer<W> {
+        self.computer
+    }
+
+    pub fn send_line(&mut self, line: &str) -> Result<(), IntcodeError> {
+        for code in encode(line)? {
+            self.computer.add_input(code);
+        }
+        Ok(())
+    }
+
+    //Runs until the program halts or wants more input than was sent. Its
+    //output stays buffered for take_text, even when it fails.
+    pub fn run(&mut self) -> Result<(), IntcodeError> {
+        loop {
+            match self.computer.run()? {
+                State::Output(_) => (),
+                State::Halted | State::AwaitingInput => return Ok(()),
+            }
+        }
+    }
+
+    //Text printed since the last call
+    pub fn take_text(&mut self) -> String {
+        let values: Vec<W> = self.computer.drain_outputs().collect();
+        render(&values)
+    }
+
+    //Plays the program from a terminal or a script: prints what it says and
+    //reads a line whenever it waits for input, until it halts or the lines
+    //run out
+    pub fn session<R: BufRead, O: Write>(
+        &mut self,
+        mut input: R,
+        mut output: O,
+    ) -> Result<(), SessionError> {
+        loop {
+            let result = self.run();
+            output.write_all(self.take_text().as_bytes())?;
+            output.flush()?;
+            result?;
+            if self.computer.is_halted() {
+                return Ok(());
+            }
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            self.send_line(line.trim_end_matches(['\n', '\r']))?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::assembler::assemble;
+    use std::io::Cursor;
+
+    //Prompts for two lines, echoes them and prints 1000 per line read
+    fn echo() -> Ascii {
+        let image = assemble(
+            "loop: out 62
+                   out 32
+             read: in [char]
+                   eq [char], 10, [flag]
+                   jt [flag], line
+                   out [char]
+                   jf 0, read
+             line: out 10
+                   add [count], 1, [count]
+                   lt [count], 2, [flag]
+                   jt [flag], loop
+                   mul [count], 1000, [count]
+                   out [count]
+                   hlt
+             char: data 0
+             flag: data 0
+             count: data 0",
+        )
+        .unwrap();
+        Ascii::new(Computer::new(image, vec![]))
+    }
+
+    #[test]
+    fn text_is_encoded_and_rendered() {
+        assert_eq!(encode::<i64>("Hi").unwrap(), vec![72, 105, 10]);
+        assert_eq!(render(&[72i64, 105, 10, -1, 300, 65]), "Hi\n-1\n300\nA");
+        assert_eq!(render(&[72i64, 128]), "H\n128\n");
+    }
+
+    #[test]
+    fn lines_can_be_sent_ahead() {
+        let mut echo = echo();
+        echo.send_line("hi").unwrap();
+        echo.run().unwrap();
+        assert_eq!(echo.take_text(), "> hi\n> ");
+        echo.send_line("yo").unwrap();
+        echo.run().unwrap();
+        assert_eq!(echo.take_text(), "yo\n2000\n");
+        assert!(echo.computer().is_halted());
+    }
+
+    #[test]
+    fn sessions_read_lines_when_asked() {
+        let mut output = Vec::new();
+        echo()
+            .session(Cursor::new("hi\r\nyo\nunread\n"), &mut output)
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "> hi\n> yo\n2000\n");
+        //Stops waiting for input when the lines run out
+        let mut echo = echo();
+        let mut output = Vec::new();
+        echo.session(Cursor::new("hi\n"), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "> hi\n> ");
+        assert!(!echo.computer().is_halted());
+    }
+
+    #[test]
+    fn errors_keep_the_text_printed() {
+        let image = assemble("out 79\nout 75\nout [rb-1]").unwrap();
+        let mut output = Vec::new();
+        let result = Ascii::new(Computer::new(image, vec![])).session(io::empty(), &mut output);
+        assert_eq!(String::from_utf8(output).unwrap(), "OK");
+        match result {
+            Err(SessionError::Intcode(IntcodeError::NegativeAddress { .. })) => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
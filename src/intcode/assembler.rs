@@ -0,0 +1,504 @@
+use super::instruction::{Operation, ParameterMode};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+//Text assembly for Intcode, the same syntax the disassembler produces:
+//
+//  const LIMIT = 10          ; named constant
+//  loop: out [counter]       ; labels end with a colon
+//        add [counter], 1, [counter]
+//        lt [counter], LIMIT, [flag]
+//        jt [flag], loop
+//        hlt
+//  counter: data 0           ; raw words
+//  flag: data 0
+//
+//Operands are immediate (`5`, `loop`), position (`[counter]`) or relative
+//(`[rb+2]`, `[rb-1]`). Values may add or subtract numbers, labels and
+//constants. A line may start with its address, as listings do, which must
+//match the address it is assembled at.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembleError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+//Numbers are wider than words so that negating one can give i64::MIN
+#[derive(Debug, Clone)]
+enum Term {
+    Number(i128),
+    Symbol(String),
+}
+
+//Sum of signed terms
+#[derive(Debug, Clone)]
+struct Expression(Vec<(bool, Term)>);
+
+#[derive(Debug, Clone)]
+struct Operand {
+    mode: ParameterMode,
+    value: Expression,
+}
+
+#[derive(Debug, Clone)]
+enum Statement {
+    Instruction(Operation, Vec<Operand>),
+    Data(Vec<Expression>),
+    Constant(String, Expression),
+}
+
+impl Statement {
+    fn size(&self) -> usize {
+        match self {
+            Statement::Instruction(operation, _) => 1 + operation.parameters(),
+            Statement::Data(values) => values.len(),
+            Statement::Constant(_, _) => 0,
+        }
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+fn parse_term(text: &str) -> Result<Term, String> {
+    if let Ok(number) = text.parse() {
+        Ok(Term::Number(number))
+    } else if is_identifier(text) && text != "rb" {
+        Ok(Term::Symbol(text.to_string()))
+    } else {
+        Err(format!("Invalid value: '{}'", text))
+    }
+}
+
+fn parse_expression(text: &str) -> Result<Expression, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("Missing value".to_string());
+    }
+    let mut terms = Vec::new();
+    let mut negative = false;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        if c == '+' || c == '-' {
+            let term = text[start..index].trim();
+            if term.is_empty() {
+                //Sign of the first term
+                if !terms.is_empty() || index != 0 {
+                    return Err(format!("Invalid value: '{}'", text));
+                }
+            } else {
+                terms.push((negative, parse_term(term)?));
+            }
+            negative = c == '-';
+            start = index + 1;
+        }
+    }
+    terms.push((negative, parse_term(text[start..].trim())?));
+    Ok(Expression(terms))
+}
+
+fn parse_operand(text: &str) -> Result<Operand, String> {
+    let text = text.trim();
+    if !text.starts_with('[') {
+        return Ok(Operand {
+            mode: ParameterMode::Immediate,
+            value: parse_expression(text)?,
+        });
+    }
+    if !text.ends_with(']') {
+        return Err(format!("Unclosed bracket: '{}'", text));
+    }
+    let inner = text[1..text.len() - 1].trim();
+    if inner == "rb" {
+        return Ok(Operand {
+            mode: ParameterMode::Relative,
+            value: Expression(vec![(false, Term::Number(0))]),
+        });
+    }
+    if inner.starts_with("rb") && inner[2..].trim_start().starts_with(&['+', '-'][..]) {
+        return Ok(Operand {
+            mode: ParameterMode::Relative,
+            value: parse_expression(&inner[2..])?,
+        });
+    }
+    Ok(Operand {
+        mode: ParameterMode::Position,
+        value: parse_expression(inner)?,
+    })
+}
+
+fn split_arguments(text: &str) -> Vec<&str> {
+    if text.trim().is_empty() {
+        Vec::new()
+    } else {
+        text.split(',').map(str::trim).collect()
+    }
+}
+
+fn parse_statement(text: &str) -> Result<Statement, String> {
+    let (keyword, rest) = match text.find(char::is_whitespace) {
+        Some(index) => (&text[..index], &text[index..]),
+        None => (text, ""),
+    };
+    match keyword {
+        "data" => {
+            let values = split_arguments(rest)
+                .into_iter()
+                .map(parse_expression)
+                .collect::<Result<Vec<_>, _>>()?;
+            if values.is_empty() {
+                return Err("Data directive without values".to_string());
+            }
+            Ok(Statement::Data(values))
+        }
+        "const" => {
+            let mut parts = rest.splitn(2, '=');
+            let name = parts.next().unwrap_or("").trim();
+            let value = parts
+                .next()
+                .ok_or_else(|| "Constant without value".to_string())?;
+            if !is_identifier(name) || name == "rb" {
+                return Err(format!("Invalid constant name: '{}'", name));
+            }
+            Ok(Statement::Constant(
+                name.to_string(),
+                parse_expression(value)?,
+            ))
+        }
+        _ => {
+            let operation = Operation::from_mnemonic(keyword)
+                .ok_or_else(|| format!("Unknown mnemonic: '{}'", keyword))?;
+            let operands = split_arguments(rest)
+                .into_iter()
+                .map(parse_operand)
+                .collect::<Result<Vec<_>, _>>()?;
+            if operands.len() != operation.parameters() {
+                return Err(format!(
+                    "'{}' takes {} operands but {} were given",
+                    keyword,
+                    operation.parameters(),
+                    operands.len()
+                ));
+            }
+            if let Some(offset) = operation.written_parameter() {
+                if operands[offset - 1].mode == ParameterMode::Immediate {
+                    return Err(format!(
+                        "'{}' writes to operand {}, which cannot be immediate",
+                        keyword, offset
+                    ));
+                }
+            }
+            Ok(Statement::Instruction(operation, operands))
+        }
+    }
+}
+
+//First symbol of the expression that is not defined yet
+fn undefined<'a>(expression: &'a Expression, symbols: &HashMap<String, i64>) -> Option<&'a str> {
+    expression.0.iter().find_map(|(_, term)| match term {
+        Term::Symbol(name) if !symbols.contains_key(name) => Some(name.as_str()),
+        _ => None,
+    })
+}
+
+fn evaluate(expression: &Expression, symbols: &HashMap<String, i64>) -> Result<i64, String> {
+    let mut total: i128 = 0;
+    for (negative, term) in &expression.0 {
+        let value = match term {
+            Term::Number(number) => *number,
+            Term::Symbol(name) => i128::from(
+                *symbols
+                    .get(name)
+                    .ok_or_else(|| format!("Undefined symbol: '{}'", name))?,
+            ),
+        };
+        let result = if *negative {
+            total.checked_sub(value)
+        } else {
+            total.checked_add(value)
+        };
+        total = result.ok_or_else(|| "Value overflows".to_string())?;
+    }
+    i64::try_from(total).map_err(|_| "Value overflows".to_string())
+}
+
+//Assembles source text into a memory image
+pub fn assemble(source: &str) -> Result<Vec<i64>, AssembleError> {
+    //First pass: parse every line and place the labels
+    let mut statements = Vec::new();
+    let mut constants = Vec::new();
+    let mut symbols: HashMap<String, i64> = HashMap::new();
+    let mut address = 0;
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |message| AssembleError {
+            line: line_number,
+            message,
+        };
+        let mut text = line.split(';').next().unwrap_or("").trim();
+        //Optional address column
+        if text.starts_with(|c: char| c.is_ascii_digit()) {
+            let end = text.find(char::is_whitespace).unwrap_or(text.len());
+            let column: usize = text[..end]
+                .parse()
+                .map_err(|_| error(format!("Invalid address: '{}'", &text[..end])))?;
+            if column != address {
+                return Err(error(format!(
+                    "Line is at address {} but says {}",
+                    address, column
+                )));
+            }
+            text = text[end..].trim_start();
+        }
+        while let Some(colon) = text.find(':') {
+            let label = text[..colon].trim();
+            if !is_identifier(label) || label == "rb" {
+                return Err(error(format!("Invalid label: '{}'", label)));
+            }
+            if constants.iter().any(|(_, name, _)| name == label)
+                || symbols.insert(label.to_string(), address as i64).is_some()
+            {
+                return Err(error(format!("Duplicate symbol: '{}'", label)));
+            }
+            text = text[colon + 1..].trim_start();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        match parse_statement(text).map_err(error)? {
+            Statement::Constant(name, value) => {
+                if symbols.contains_key(&name)
+                    || constants.iter().any(|(_, other, _)| *other == name)
+                {
+                    return Err(error(format!("Duplicate symbol: '{}'", name)));
+                }
+                constants.push((line_number, name, value));
+            }
+            statement => {
+                address += statement.size();
+                statements.push((line_number, statement));
+            }
+        }
+    }
+
+    //Constants may use labels and constants defined after them, so they are
+    //resolved once every label is placed, each as soon as what it uses is
+    while !constants.is_empty() {
+        let before = constants.len();
+        let mut index = 0;
+        while index < constants.len() {
+            let (line_number, name, value) = &constants[index];
+            if undefined(value, &symbols).is_some() {
+                index += 1;
+                continue;
+            }
+            let value = evaluate(value, &symbols).map_err(|message| AssembleError {
+                line: *line_number,
+                message,
+            })?;
+            symbols.insert(name.clone(), value);
+            constants.remove(index);
+        }
+        if constants.len() == before {
+            let (line, _, value) = &constants[0];
+            let name = undefined(value, &symbols).unwrap_or_default();
+            let message = if constants.iter().any(|(_, other, _)| other == name) {
+                format!("Circular constant: '{}'", name)
+            } else {
+                format!("Undefined symbol: '{}'", name)
+            };
+            return Err(AssembleError {
+                line: *line,
+                message,
+            });
+        }
+    }
+
+    //Second pass: emit the words
+    let mut image = Vec::with_capacity(address);
+    for (line_number, statement) in statements {
+        let error = |message| AssembleError {
+            line: line_number,
+            message,
+        };
+        match statement {
+            Statement::Constant(_, _) => unreachable!("Constants are resolved before"),
+            Statement::Data(values) => {
+                for value in values {
+                    image.push(evaluate(&value, &symbols).map_err(error)?);
+                }
+            }
+            Statement::Instruction(operation, operands) => {
+                let modes = operands
+                    .iter()
+                    .rev()
+                    .fold(0, |modes, operand| modes * 10 + operand.mode.digit());
+                image.push(modes * 100 + operation.opcode());
+                for operand in operands {
+                    image.push(evaluate(&operand.value, &symbols).map_err(error)?);
+                }
+            }
+        }
+    }
+    Ok(image)
+}
+
+//Machine loaded with the assembled source, for tests of other modules
+#[cfg(test)]
+pub(crate) fn assembled(source: &str) -> crate::intcode::Computer {
+    crate::intcode::Computer::new(assemble(source).unwrap(), vec![])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::disassembler::disassemble;
+    use crate::intcode::Computer;
+
+    #[test]
+    fn assembles_modes_and_labels() {
+        let source = "
+            const LIMIT = 3
+            loop: out [counter]       ; print the counter
+                  add [counter], 1, [counter]
+                  lt [counter], LIMIT, [flag]
+                  jt [flag], loop
+                  hlt
+            counter: data 0
+            flag: data 0
+        ";
+        let image = assemble(source).unwrap();
+        assert_eq!(
+            image,
+            vec![4, 14, 1001, 14, 1, 14, 1007, 14, 3, 15, 1005, 15, 0, 99, 0, 0]
+        );
+        let mut computer = Computer::new(image, vec![]);
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.outputs(), &[0, 1, 2]);
+    }
+
+    #[test]
+    fn relative_operands_and_expressions() {
+        let image = assemble("arb 10\nin [rb-2]\nout [rb+end-1]\nend: hlt").unwrap();
+        assert_eq!(image, vec![109, 10, 203, -2, 204, 5, 99]);
+        assert_eq!(assemble("out [rb]").unwrap(), vec![204, 0]);
+    }
+
+    #[test]
+    fn listings_assemble_back_to_the_image() {
+        let image = vec![
+            3,
+            8,
+            1001,
+            8,
+            10,
+            8,
+            105,
+            1,
+            0,
+            0,
+            21,
+            30,
+            99999,
+            3,
+            9,
+            1001,
+            9,
+            5,
+            9,
+            4,
+            9,
+            99,
+            1105,
+            1,
+            0,
+            21202,
+            -1,
+            7,
+            3,
+            209,
+            -3,
+            104,
+            i64::MIN,
+            99,
+            i64::MIN,
+            i64::MAX,
+        ];
+        let listing = disassemble(&image).to_string();
+        assert_eq!(assemble(&listing), Ok(image));
+        assert_eq!(
+            assemble("data -9223372036854775808 + 1"),
+            Ok(vec![i64::MIN + 1])
+        );
+        let error = assemble("data 9223372036854775808").unwrap_err();
+        assert_eq!(error.message, "Value overflows");
+    }
+
+    #[test]
+    fn errors_report_their_line() {
+        let error = assemble("out 1\nfoo 2").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.message, "Unknown mnemonic: 'foo'");
+        let error = assemble("add 1, 2").unwrap_err();
+        assert_eq!(error.message, "'add' takes 3 operands but 2 were given");
+        let error = assemble("jt 1, nowhere").unwrap_err();
+        assert_eq!(error.message, "Undefined symbol: 'nowhere'");
+        let error = assemble("a: hlt\na: hlt").unwrap_err();
+        assert_eq!(error.to_string(), "Line 2: Duplicate symbol: 'a'");
+        let error = assemble("0 hlt\n3 hlt").unwrap_err();
+        assert_eq!(error.message, "Line is at address 1 but says 3");
+    }
+
+    #[test]
+    fn written_operands_cannot_be_immediate() {
+        let error = assemble("hlt\nadd 1, 2, 3").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Line 2: 'add' writes to operand 3, which cannot be immediate"
+        );
+        let error = assemble("in 5").unwrap_err();
+        assert_eq!(
+            error.message,
+            "'in' writes to operand 1, which cannot be immediate"
+        );
+        assert_eq!(assemble("in [rb+5]").unwrap(), vec![203, 5]);
+        //Such instructions are listed as data, so listings still assemble
+        let image = vec![99, 11101, 1, 2, 3];
+        assert_eq!(assemble(&disassemble(&image).to_string()), Ok(image));
+    }
+
+    #[test]
+    fn constants_can_be_used_before_they_are_defined() {
+        let source = "
+            out STEP
+            out END
+            const END = last + STEP
+            const STEP = 2
+            last: hlt
+        ";
+        assert_eq!(assemble(source).unwrap(), vec![104, 2, 104, 6, 99]);
+        let error = assemble("const A = B\nconst B = A + 1").unwrap_err();
+        assert_eq!(error.to_string(), "Line 1: Circular constant: 'B'");
+        let error = assemble("const A = 1\nconst A = 2").unwrap_err();
+        assert_eq!(error.to_string(), "Line 2: Duplicate symbol: 'A'");
+        let error = assemble("const A = nowhere").unwrap_err();
+        assert_eq!(error.message, "Undefined symbol: 'nowhere'");
+    }
+}
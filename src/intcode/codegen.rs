@@ -0,0 +1,535 @@
+use super::instruction::{Instruction, Operation, ParameterMode};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::ops::Range;
+
+//Ahead-of-time translation of Intcode images into Rust. The code is split
+//into basic blocks, each one becoming a match arm of a dispatch loop over the
+//program counter. Blocks only trust the words they were translated from:
+//once the program writes into one it runs on the interpreter, as does code
+//reached through jumps computed at run time.
+//
+//The generated module needs the compiled::Machine runtime, so it is meant to
+//be included into this crate. The build script shares this file through a
+//path attribute and can only rely on the instruction module.
+
+struct Step {
+    address: usize,
+    instruction: Instruction,
+    parameters: Vec<i64>,
+}
+
+impl Step {
+    fn next(&self) -> usize {
+        self.address + 1 + self.parameters.len()
+    }
+
+    fn mode(&self, offset: usize) -> ParameterMode {
+        self.instruction.mode(offset)
+    }
+
+    //Whether the block has to end after this instruction
+    fn ends_block(&self) -> bool {
+        match self.instruction.operation {
+            Operation::JumpIfTrue
+            | Operation::JumpIfFalse
+            | Operation::Save
+            | Operation::Output
+            | Operation::Halt => true,
+            operation => match operation.written_parameter() {
+                Some(offset) => self.mode(offset) == ParameterMode::Immediate,
+                None => false,
+            },
+        }
+    }
+}
+
+struct Block {
+    start: usize,
+    end: usize,
+    steps: Vec<Step>,
+}
+
+fn decode(image: &[i64], address: usize) -> Option<Step> {
+    let instruction = Instruction::new(*image.get(address)?).ok()?;
+    let end = address + 1 + instruction.operation.parameters();
+    if end > image.len() {
+        return None;
+    }
+    Some(Step {
+        address,
+        instruction,
+        parameters: image[address + 1..end].to_vec(),
+    })
+}
+
+fn immediate_target(step: &Step) -> Option<usize> {
+    if step.mode(2) == ParameterMode::Immediate && step.parameters[1] >= 0 {
+        Some(step.parameters[1] as usize)
+    } else {
+        None
+    }
+}
+
+//Follows the control flow from an address, collecting the instructions
+//reached and the addresses that have to start a block
+fn explore(
+    image: &[i64],
+    start: usize,
+    reached: &mut BTreeSet<usize>,
+    leaders: &mut BTreeSet<usize>,
+) {
+    leaders.insert(start);
+    let mut pending = vec![start];
+    while let Some(address) = pending.pop() {
+        if reached.contains(&address) {
+            continue;
+        }
+        let step = match decode(image, address) {
+            Some(step) => step,
+            None => continue,
+        };
+        reached.insert(address);
+        match step.instruction.operation {
+            Operation::Halt => continue,
+            Operation::JumpIfTrue | Operation::JumpIfFalse => {
+                if let Some(target) = immediate_target(&step) {
+                    leaders.insert(target);
+                    pending.push(target);
+                }
+            }
+            //Execution resumes at an input that is missing and after an output
+            Operation::Save => {
+                leaders.insert(address);
+            }
+            _ => (),
+        }
+        if step.ends_block() {
+            leaders.insert(step.next());
+        }
+        pending.push(step.next());
+    }
+}
+
+//Splits the code into basic blocks. Besides the code reachable from address
+//0, words left over that decode as instructions are translated too, since
+//jumps computed at run time may land on them.
+fn blocks(image: &[i64]) -> Vec<Block> {
+    let mut reached = BTreeSet::new();
+    let mut leaders = BTreeSet::new();
+    explore(image, 0, &mut reached, &mut leaders);
+    let mut covered = vec![false; image.len()];
+    let mut address = 0;
+    while address < image.len() {
+        if let Some(step) = decode(image, address) {
+            let free = !covered[address..step.next()].iter().any(|word| *word);
+            if !reached.contains(&address) && free {
+                explore(image, address, &mut reached, &mut leaders);
+            }
+            if reached.contains(&address) {
+                covered[address..step.next()]
+                    .iter_mut()
+                    .for_each(|word| *word = true);
+                address = step.next();
+                continue;
+            }
+        }
+        address += 1;
+    }
+
+    let mut blocks = Vec::new();
+    for start in leaders
+        .iter()
+        .cloned()
+        .filter(|start| reached.contains(start))
+    {
+        let mut steps = Vec::new();
+        let mut address = start;
+        while reached.contains(&address) && (address == start || !leaders.contains(&address)) {
+            let step = decode(image, address).expect("Reached addresses decode");
+            address = step.next();
+            let ends_block = step.ends_block();
+            steps.push(step);
+            if ends_block {
+                break;
+            }
+        }
+        blocks.push(Block {
+            start,
+            end: address,
+            steps,
+        });
+    }
+    blocks
+}
+
+fn literal(value: i64) -> String {
+    format!("{}i64", value)
+}
+
+//Relative base plus an offset, for the instruction at the given address
+fn relative(address: usize, offset: i64) -> String {
+    format!("m.relative({}, {})?", address, literal(offset))
+}
+
+//Expression reading a parameter
+fn operand(step: &Step, offset: usize) -> String {
+    let parameter = step.parameters[offset - 1];
+    match step.mode(offset) {
+        ParameterMode::Immediate => literal(parameter),
+        ParameterMode::Position => format!("m.load({}, {})?", step.address, literal(parameter)),
+        ParameterMode::Relative => format!(
+            "m.load({}, {})?",
+            step.address,
+            relative(step.address, parameter)
+        ),
+    }
+}
+
+//Negative literals need parentheses before a method call
+fn receiver(expression: String) -> String {
+    if expression.starts_with('-') {
+        format!("({})", expression)
+    } else {
+        expression
+    }
+}
+
+//Expression for the address a parameter writes to, None in immediate mode
+fn target(step: &Step, offset: usize) -> Option<String> {
+    let parameter = step.parameters[offset - 1];
+    match step.mode(offset) {
+        ParameterMode::Immediate => None,
+        ParameterMode::Position => Some(literal(parameter)),
+        ParameterMode::Relative => Some(relative(step.address, parameter)),
+    }
+}
+
+fn immediate(step: &Step, offset: usize) -> Option<i64> {
+    match step.mode(offset) {
+        ParameterMode::Immediate => Some(step.parameters[offset - 1]),
+        _ => None,
+    }
+}
+
+fn write_immediate_error(address: usize) -> String {
+    format!(
+        "return Err(IntcodeError::WriteInImmediateMode {{ address: {} }});",
+        address
+    )
+}
+
+//Statements storing `value` through the written parameter
+fn store(code: &mut Vec<String>, step: &Step, value: String) {
+    match target(step, 3) {
+        Some(target) => code.push(format!(
+            "m.store({}, {}, {})?;",
+            step.address, target, value
+        )),
+        None => {
+            code.push(format!("let _ = {};", value));
+            code.push(write_immediate_error(step.address));
+        }
+    }
+}
+
+fn translate(step: &Step) -> Vec<String> {
+    let address = step.address;
+    let overflow = format!("IntcodeError::Overflow {{ address: {} }}", address);
+    let mut code = vec![format!("m.set_position({});", address)];
+    match step.instruction.operation {
+        Operation::Add | Operation::Multiply => {
+            let (method, constant) = match step.instruction.operation {
+                Operation::Add => (
+                    "checked_add",
+                    immediate(step, 1)
+                        .zip(immediate(step, 2))
+                        .map(|(a, b)| a.checked_add(b)),
+                ),
+                _ => (
+                    "checked_mul",
+                    immediate(step, 1)
+                        .zip(immediate(step, 2))
+                        .map(|(a, b)| a.checked_mul(b)),
+                ),
+            };
+            let value = match constant {
+                Some(Some(value)) => literal(value),
+                Some(None) => {
+                    code.push(format!("return Err({});", overflow));
+                    return code;
+                }
+                None => format!(
+                    "{}.{}({}).ok_or({})?",
+                    receiver(operand(step, 1)),
+                    method,
+                    operand(step, 2),
+                    overflow
+                ),
+            };
+            store(&mut code, step, value);
+        }
+        Operation::LessThan | Operation::Equals => {
+            let symbol = if step.instruction.operation == Operation::LessThan {
+                "<"
+            } else {
+                "=="
+            };
+            let value = match (immediate(step, 1), immediate(step, 2)) {
+                (Some(a), Some(b)) => {
+                    let holds = if symbol == "<" { a < b } else { a == b };
+                    literal(holds as i64)
+                }
+                _ => format!(
+                    "({} {} {}) as i64",
+                    operand(step, 1),
+                    symbol,
+                    operand(step, 2)
+                ),
+            };
+            store(&mut code, step, value);
+        }
+        Operation::Save => {
+            code.push("if !m.has_input() {".to_string());
+            code.push("    return Ok(State::AwaitingInput);".to_string());
+            code.push("}".to_string());
+            match target(step, 1) {
+                Some(target) => code.push(format!("m.store_input({}, {})?;", address, target)),
+                None => {
+                    code.push(write_immediate_error(address));
+                    return code;
+                }
+            }
+        }
+        Operation::Output => {
+            code.push(format!("let value = {};", operand(step, 1)));
+            code.push("m.output(value);".to_string());
+            code.push(format!("m.set_position({});", step.next()));
+            code.push("m.count_step();".to_string());
+            code.push("return Ok(State::Output(value));".to_string());
+            return code;
+        }
+        Operation::JumpIfTrue | Operation::JumpIfFalse => {
+            let jump = format!("m.jump({}, {})?;", address, operand(step, 2));
+            let fall = format!("m.set_position({});", step.next());
+            let taken =
+                |value: i64| (value != 0) == (step.instruction.operation == Operation::JumpIfTrue);
+            match immediate(step, 1) {
+                Some(value) if taken(value) => code.push(jump),
+                Some(_) => code.push(fall),
+                None => {
+                    let comparison = if step.instruction.operation == Operation::JumpIfTrue {
+                        "!="
+                    } else {
+                        "=="
+                    };
+                    code.push(format!("if {} {} 0 {{", operand(step, 1), comparison));
+                    code.push(format!("    {}", jump));
+                    code.push("} else {".to_string());
+                    code.push(format!("    {}", fall));
+                    code.push("}".to_string());
+                }
+            }
+        }
+        Operation::AdjustRelativeBase => {
+            code.push(format!("let offset = {};", operand(step, 1)));
+            code.push("m.adjust_relative_base(offset)?;".to_string());
+        }
+        Operation::Halt => {
+            code.push("m.halt();".to_string());
+            code.push("m.count_step();".to_string());
+            code.push("return Ok(State::Halted);".to_string());
+            return code;
+        }
+    }
+    code.push("m.count_step();".to_string());
+    code
+}
+
+//Whether the instruction may write to one of the addresses
+fn may_write(step: &Step, addresses: Range<usize>) -> bool {
+    let offset = match step.instruction.operation.written_parameter() {
+        Some(offset) => offset,
+        None => return false,
+    };
+    let parameter = step.parameters[offset - 1];
+    match step.mode(offset) {
+        ParameterMode::Position => parameter >= 0 && addresses.contains(&(parameter as usize)),
+        ParameterMode::Relative => !addresses.is_empty(),
+        ParameterMode::Immediate => false,
+    }
+}
+
+//Rust source of a module running the image
+pub fn transpile(image: &[i64], name: &str) -> String {
+    let blocks = blocks(image);
+    let mut source = String::new();
+    let words: Vec<_> = image.iter().map(|word| word.to_string()).collect();
+    let ranges: Vec<_> = blocks
+        .iter()
+        .map(|block| format!("({}, {}, {})", block.start, block.end, block.steps.len()))
+        .collect();
+    writeln!(source, "pub mod {} {{", name).unwrap();
+    writeln!(source, "    use super::Machine;").unwrap();
+    writeln!(
+        source,
+        "    use crate::intcode::{{Computer, IntcodeError, State}};"
+    )
+    .unwrap();
+    writeln!(source).unwrap();
+    writeln!(
+        source,
+        "    pub static IMAGE: [i64; {}] = [{}];",
+        image.len(),
+        words.join(", ")
+    )
+    .unwrap();
+    writeln!(source).unwrap();
+    writeln!(
+        source,
+        "    //Start, end and length of every translated block"
+    )
+    .unwrap();
+    writeln!(
+        source,
+        "    pub static BLOCKS: [(usize, usize, u64); {}] = [{}];",
+        blocks.len(),
+        ranges.join(", ")
+    )
+    .unwrap();
+    writeln!(source).unwrap();
+    writeln!(source, "    pub fn machine(input: Vec<i64>) -> Machine {{").unwrap();
+    writeln!(source, "        Machine::new(input, &IMAGE, &BLOCKS, run)").unwrap();
+    writeln!(source, "    }}").unwrap();
+    writeln!(source).unwrap();
+    writeln!(
+        source,
+        "    pub fn from_computer(computer: Computer<i64>) -> Machine {{"
+    )
+    .unwrap();
+    writeln!(
+        source,
+        "        Machine::from_computer(computer, &IMAGE, &BLOCKS, run)"
+    )
+    .unwrap();
+    writeln!(source, "    }}").unwrap();
+    writeln!(source).unwrap();
+    writeln!(
+        source,
+        "    fn run(m: &mut Machine) -> Result<State<i64>, IntcodeError> {{"
+    )
+    .unwrap();
+    writeln!(source, "        loop {{").unwrap();
+    writeln!(source, "            match m.position() {{").unwrap();
+    for (index, block) in blocks.iter().enumerate() {
+        writeln!(
+            source,
+            "                {} if m.enter({}) => {{",
+            block.start, index
+        )
+        .unwrap();
+        for step in &block.steps {
+            writeln!(
+                source,
+                "                    //{}: {}",
+                step.address,
+                step.instruction.operation.mnemonic()
+            )
+            .unwrap();
+            for line in translate(step) {
+                writeln!(source, "                    {}", line).unwrap();
+            }
+            //A write into the rest of the block leaves it to the interpreter
+            if may_write(step, step.next()..block.end) {
+                writeln!(source, "                    if m.is_dirty({}) {{", index).unwrap();
+                writeln!(
+                    source,
+                    "                        m.set_position({});",
+                    step.next()
+                )
+                .unwrap();
+                writeln!(source, "                        continue;").unwrap();
+                writeln!(source, "                    }}").unwrap();
+            }
+        }
+        //Jumps and returns already left the block
+        let last = block.steps.last().expect("Blocks are never empty");
+        let jumps = match last.instruction.operation {
+            Operation::JumpIfTrue | Operation::JumpIfFalse => true,
+            _ => translate(last)
+                .last()
+                .is_some_and(|line| line.starts_with("return ")),
+        };
+        if !jumps {
+            writeln!(source, "                    m.set_position({});", block.end).unwrap();
+        }
+        writeln!(source, "                }}").unwrap();
+    }
+    writeln!(source, "                _ => {{").unwrap();
+    writeln!(
+        source,
+        "                    if let Some(state) = m.interpret()? {{"
+    )
+    .unwrap();
+    writeln!(source, "                        return Ok(state);").unwrap();
+    writeln!(source, "                    }}").unwrap();
+    writeln!(source, "                }}").unwrap();
+    writeln!(source, "            }}").unwrap();
+    writeln!(source, "        }}").unwrap();
+    writeln!(source, "    }}").unwrap();
+    writeln!(source, "}}").unwrap();
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_end_at_jumps_io_and_targets() {
+        //in [9]; out [9]; jt [9], 0; hlt
+        let image = [3, 9, 4, 9, 1005, 9, 0, 99, 0, 0];
+        let blocks = blocks(&image);
+        let bounds: Vec<_> = blocks
+            .iter()
+            .map(|block| (block.start, block.end))
+            .collect();
+        assert_eq!(bounds, vec![(0, 2), (2, 4), (4, 7), (7, 8)]);
+    }
+
+    #[test]
+    fn words_left_over_are_translated() {
+        //The jump target is only known at run time
+        let image = [6, 9, 8, 99, 104, 1, 99, 42, 3, 0];
+        let blocks = blocks(&image);
+        let bounds: Vec<_> = blocks
+            .iter()
+            .map(|block| (block.start, block.end))
+            .collect();
+        assert_eq!(bounds, vec![(0, 3), (3, 4), (4, 6), (6, 7), (8, 10)]);
+    }
+
+    #[test]
+    fn constant_operations_are_folded() {
+        let source = transpile(&[1108, 5, 5, 5, 1101, 2, 3, 0, 99], "sample");
+        assert!(source.contains("m.store(0, 5i64, 1i64)?;"));
+        assert!(source.contains("m.store(4, 0i64, 5i64)?;"));
+        let source = transpile(&[1102, 9223372036854775807, 2, 0, 99], "sample");
+        assert!(source.contains("return Err(IntcodeError::Overflow { address: 0 });"));
+    }
+
+    #[test]
+    fn relative_addresses_are_checked() {
+        let source = transpile(&[204, -1, 99], "sample");
+        assert!(source.contains("m.load(0, m.relative(0, -1i64)?)?"));
+    }
+
+    #[test]
+    fn writes_inside_a_block_check_it() {
+        //add 1, -1, [4]; out 7; hlt
+        let source = transpile(&[1101, 1, -1, 4, 104, 7, 99], "sample");
+        assert!(source.contains("if m.is_dirty(0) {"));
+        let source = transpile(&[1101, 1, -1, 9, 99], "sample");
+        assert!(!source.contains("is_dirty"));
+    }
+}
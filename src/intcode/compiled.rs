@@ -0,0 +1,385 @@
+use super::computer::{Computer, State};
+use super::disassembler::decode;
+use super::error::IntcodeError;
+use super::instruction::ParameterMode;
+
+//Programs translated by the build script, one module per file in input/
+include!(concat!(env!("OUT_DIR"), "/compiled.rs"));
+
+//Translated block as start, end and number of instructions
+pub type BlockRange = (usize, usize, u64);
+
+type Program = fn(&mut Machine) -> Result<State<i64>, IntcodeError>;
+
+//Runs a program translated to Rust, falling back to the interpreter for code
+//that changed since the translation or was never translated
+pub struct Machine {
+    computer: Computer<i64>,
+    blocks: &'static [BlockRange],
+    //Blocks whose words no longer match the translated image
+    dirty: Vec<bool>,
+    //Whether an address belongs to some translated block
+    code: Vec<bool>,
+    program: Program,
+}
+
+impl Machine {
+    //Fresh machine loaded with the translated image
+    pub fn new(
+        input: Vec<i64>,
+        image: &'static [i64],
+        blocks: &'static [BlockRange],
+        program: Program,
+    ) -> Self {
+        let mut code = vec![false; image.len()];
+        for (start, end, _) in blocks {
+            code[*start..*end].iter_mut().for_each(|cell| *cell = true);
+        }
+        Machine {
+            computer: Computer::new(image.to_vec(), input),
+            blocks,
+            dirty: vec![false; blocks.len()],
+            code,
+            program,
+        }
+    }
+
+    //Runs an existing machine on the translation of an image, only trusting
+    //the blocks whose words it still holds
+    pub fn from_computer(
+        computer: Computer<i64>,
+        image: &'static [i64],
+        blocks: &'static [BlockRange],
+        program: Program,
+    ) -> Self {
+        let mut machine = Machine::new(Vec::new(), image, blocks, program);
+        for (index, (start, end, _)) in blocks.iter().enumerate() {
+            machine.dirty[index] =
+                (*start..*end).any(|address| computer.read(address) != image[address]);
+        }
+        machine.computer = computer;
+        machine
+    }
+
+    pub fn computer(&self) -> &Computer<i64> {
+        &self.computer
+    }
+
+    pub fn into_computer(self) -> Computer<i64> {
+        self.computer
+    }
+
+    pub fn add_input(&mut self, value: i64) {
+        self.computer.add_input(value);
+    }
+
+    pub fn outputs(&self) -> &[i64] {
+        self.computer.outputs()
+    }
+
+    pub fn write(&mut self, address: usize, value: i64) -> Result<(), IntcodeError> {
+        self.computer.write(address, value)?;
+        self.touch(address);
+        Ok(())
+    }
+
+    //Same contract as Computer::run
+    pub fn run(&mut self) -> Result<State<i64>, IntcodeError> {
+        let program = self.program;
+        program(self)
+    }
+
+    //Same contract as Computer::run_to_halt
+    pub fn run_to_halt(&mut self) -> Result<(), IntcodeError> {
+        loop {
+            match self.run()? {
+                State::Halted => return Ok(()),
+                State::AwaitingInput => {
+                    return Err(IntcodeError::InputExhausted {
+                        address: self.position(),
+                    })
+                }
+                State::Output(_) => (),
+            }
+        }
+    }
+
+    //Marks every block holding the address as changed
+    fn touch(&mut self, address: usize) {
+        if address < self.code.len() && self.code[address] {
+            for (index, (start, end, _)) in self.blocks.iter().enumerate() {
+                if (*start..*end).contains(&address) {
+                    self.dirty[index] = true;
+                }
+            }
+        }
+    }
+
+    //Operations used by the generated code
+
+    pub fn position(&self) -> usize {
+        self.computer.position()
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.computer.relative_base()
+    }
+
+    //Relative base plus an offset, for the instruction at the given address
+    pub fn relative(&self, at: usize, offset: i64) -> Result<i64, IntcodeError> {
+        self.relative_base()
+            .checked_add(offset)
+            .ok_or(IntcodeError::RelativeBaseOverflow { address: at })
+    }
+
+    pub fn set_position(&mut self, position: usize) {
+        self.computer.set_position(position);
+    }
+
+    //Whether a translated block can run from its start
+    pub fn enter(&self, block: usize) -> bool {
+        if self.dirty[block] || self.computer.is_halted() || self.computer.out_of_time() {
+            return false;
+        }
+        match self.computer.step_limit() {
+            Some(limit) => self.computer.steps() + self.blocks[block].2 <= limit,
+            None => true,
+        }
+    }
+
+    //Whether the program wrote into a block since it was translated
+    pub fn is_dirty(&self, block: usize) -> bool {
+        self.dirty[block]
+    }
+
+    fn checked_address(at: usize, address: i64) -> Result<usize, IntcodeError> {
+        if address < 0 {
+            Err(IntcodeError::NegativeAddress {
+                address: at,
+                target: address,
+            })
+        } else {
+            Ok(address as usize)
+        }
+    }
+
+    pub fn load(&self, at: usize, address: i64) -> Result<i64, IntcodeError> {
+        Ok(self.computer.read(Machine::checked_address(at, address)?))
+    }
+
+    pub fn store(&mut self, at: usize, address: i64, value: i64) -> Result<(), IntcodeError> {
+        self.write(Machine::checked_address(at, address)?, value)
+    }
+
+    pub fn has_input(&self) -> bool {
+        self.computer.pending_input().next().is_some()
+    }
+
+    pub fn store_input(&mut self, at: usize, address: i64) -> Result<(), IntcodeError> {
+        let address = Machine::checked_address(at, address)?;
+        let value = self.computer.take_input().expect("Input is checked first");
+        self.write(address, value)
+    }
+
+    pub fn output(&mut self, value: i64) {
+        self.computer.push_output(value);
+    }
+
+    pub fn jump(&mut self, at: usize, target: i64) -> Result<(), IntcodeError> {
+        let target = Machine::checked_address(at, target)?;
+        self.computer.set_position(target);
+        Ok(())
+    }
+
+    pub fn adjust_relative_base(&mut self, offset: i64) -> Result<(), IntcodeError> {
+        self.computer.adjust_relative_base(offset)
+    }
+
+    pub fn halt(&mut self) {
+        self.computer.halt();
+    }
+
+    pub fn count_step(&mut self) {
+        self.computer.count_step();
+    }
+
+    //Executes one instruction on the interpreter
+    pub fn interpret(&mut self) -> Result<Option<State<i64>>, IntcodeError> {
+        let written = self.written_address();
+        let state = self.computer.step()?;
+        if let Some(address) = written {
+            self.touch(address);
+        }
+        Ok(state)
+    }
+
+    //Cell the next instruction would write to
+    fn written_address(&self) -> Option<usize> {
+        let decoded = decode(|address| self.computer.read(address), self.position())?;
+        let offset = decoded.instruction.operation.written_parameter()?;
+        let operand = &decoded.operands[offset - 1];
+        let address = match operand.mode {
+            ParameterMode::Position => operand.value,
+            //Overflows are reported when the interpreter executes it
+            ParameterMode::Relative => self.relative_base().checked_add(operand.value)?,
+            ParameterMode::Immediate => return None,
+        };
+        if address < 0 {
+            None
+        } else {
+            Some(address as usize)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+    use std::time::Duration;
+
+    //Translated amplifier, its image, first phase and best signal
+    type Example = (fn(Vec<i64>) -> Machine, &'static [i64], i64, i64);
+
+    fn interpreted(image: &[i64], input: Vec<i64>) -> (Result<(), IntcodeError>, Computer) {
+        let mut computer = Computer::new(image.to_vec(), input);
+        (computer.run_to_halt(), computer)
+    }
+
+    fn check_day5(input: i64) {
+        let mut machine = day5::machine(vec![input]);
+        let (result, computer) = interpreted(&day5::IMAGE, vec![input]);
+        assert_eq!(machine.run_to_halt(), result);
+        assert_eq!(machine.outputs(), computer.outputs());
+        assert_eq!(machine.computer().steps(), computer.steps());
+        assert_eq!(
+            machine.computer().snapshot().to_string(),
+            computer.snapshot().to_string()
+        );
+    }
+
+    #[test]
+    fn day5_diagnostics_match_the_interpreter() {
+        check_day5(1);
+        check_day5(5);
+        assert_eq!(day5::machine(vec![5]).run(), Ok(State::Output(14110739)));
+    }
+
+    //Signal out of the amplifier chain for one phase setting sequence,
+    //compiled and interpreted
+    fn chain<F>(phases: &[i64], make: F, image: &[i64]) -> (i64, i64)
+    where
+        F: Fn(Vec<i64>) -> Machine,
+    {
+        let mut compiled: Vec<_> = phases.iter().map(|phase| make(vec![*phase])).collect();
+        let mut interpreted: Vec<_> = phases
+            .iter()
+            .map(|phase| Computer::new(image.to_vec(), vec![*phase]))
+            .collect();
+        let (mut signal, mut expected) = (0, 0);
+        loop {
+            for (machine, computer) in compiled.iter_mut().zip(interpreted.iter_mut()) {
+                machine.add_input(signal);
+                computer.add_input(expected);
+                let state = machine.run().unwrap();
+                assert_eq!(state, computer.run().unwrap());
+                match state {
+                    State::Output(value) => {
+                        signal = value;
+                        expected = *computer.last_output().unwrap();
+                    }
+                    _ => return (signal, expected),
+                }
+            }
+        }
+    }
+
+    fn check_day7(make: fn(Vec<i64>) -> Machine, image: &[i64], phases: Vec<i64>) -> i64 {
+        phases
+            .into_iter()
+            .permutations(5)
+            .map(|phases| {
+                let (signal, expected) = chain(&phases, make, image);
+                assert_eq!(signal, expected);
+                signal
+            })
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn day7_amplifiers_match_the_interpreter() {
+        assert_eq!(
+            check_day7(day7::machine, &day7::IMAGE, (0..5).collect()),
+            45730
+        );
+        assert_eq!(
+            check_day7(day7::machine, &day7::IMAGE, (5..10).collect()),
+            5406484
+        );
+        let examples: [Example; 5] = [
+            (day7_example1::machine, &day7_example1::IMAGE, 0, 43210),
+            (day7_example2::machine, &day7_example2::IMAGE, 0, 54321),
+            (day7_example3::machine, &day7_example3::IMAGE, 0, 65210),
+            (day7_example4::machine, &day7_example4::IMAGE, 5, 139629729),
+            (day7_example5::machine, &day7_example5::IMAGE, 5, 18216),
+        ];
+        for (make, image, first, signal) in examples.iter() {
+            let phases = (*first..*first + 5).collect();
+            assert_eq!(check_day7(*make, image, phases), *signal);
+        }
+    }
+
+    #[test]
+    fn loops_match_the_interpreter() {
+        let mut machine = sum_loop::machine(vec![1000]);
+        let (result, computer) = interpreted(&sum_loop::IMAGE, vec![1000]);
+        assert_eq!(machine.run_to_halt(), result);
+        assert_eq!(machine.outputs(), &[3 * 500 * 1001]);
+        assert_eq!(machine.computer().steps(), computer.steps());
+    }
+
+    #[test]
+    fn time_limit_stops_translated_code() {
+        let mut computer = Computer::new(sum_loop::IMAGE.to_vec(), vec![1000]);
+        computer.set_time_limit(Duration::from_secs(0));
+        let mut machine = sum_loop::from_computer(computer);
+        assert!(machine.run().unwrap_err().is_budget_exceeded());
+        assert_eq!(machine.computer().steps(), 0);
+    }
+
+    #[test]
+    fn writes_later_in_the_block_are_seen() {
+        //Overwrites its output with a halt before reaching it
+        let mut machine = self_modify::machine(vec![]);
+        let (result, computer) = interpreted(&self_modify::IMAGE, vec![]);
+        assert_eq!(machine.run_to_halt(), result);
+        assert_eq!(machine.outputs(), computer.outputs());
+        assert!(machine.outputs().is_empty());
+        assert_eq!(machine.computer().steps(), computer.steps());
+    }
+
+    #[test]
+    fn relative_base_overflow_is_an_error() {
+        let mut computer = Computer::new(vec![109, i64::MAX, 99], vec![]);
+        computer.run_to_halt().unwrap();
+        let machine = sum_loop::from_computer(computer);
+        assert_eq!(
+            machine.relative(4, 1),
+            Err(IntcodeError::RelativeBaseOverflow { address: 4 })
+        );
+        assert_eq!(machine.relative(4, -1), Ok(i64::MAX - 1));
+    }
+
+    #[test]
+    fn writes_into_code_fall_back_to_the_interpreter() {
+        let mut machine = day7_example1::machine(vec![]);
+        //Turn the first input into an output of its address
+        machine.write(0, 104).unwrap();
+        assert_eq!(machine.run(), Ok(State::Output(15)));
+        let computer = Computer::new(day7_example1::IMAGE.to_vec(), vec![1, 2]);
+        let mut machine = day7_example1::from_computer(computer);
+        machine.run_to_halt().unwrap();
+        assert_eq!(machine.outputs(), &[21]);
+    }
+}
@@ -0,0 +1,1080 @@
+use super::engine::{DecodeCache, Engine};
+use super::error::IntcodeError;
+use super::instruction::{Instruction, Operation, ParameterMode};
+use super::io::{InputSource, OutputSink};
+use super::memory::{Memory, MemoryError};
+use super::snapshot::Snapshot;
+use super::trace::{TraceEvent, Tracer};
+use super::word::Word;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+//Instructions executed between two looks at the clock
+const CLOCK_INTERVAL: u64 = 1024;
+
+//Reason why a running machine gave control back to its caller
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum State<W> {
+    Halted,
+    AwaitingInput,
+    Output(W),
+}
+
+//What it takes to reverse one executed instruction
+#[derive(Debug, Clone)]
+struct Undo<W> {
+    position: usize,
+    relative_base: i64,
+    //Cell overwritten and the value it held
+    write: Option<(usize, W)>,
+    input: Option<W>,
+    output: bool,
+}
+
+pub struct Computer<W: Word = i64> {
+    memory: Memory<W>,
+    current_position: usize,
+    current_instruction: Option<Instruction>,
+    relative_base: i64,
+    finished: bool,
+    input: VecDeque<W>,
+    outputs: Vec<W>,
+    steps: u64,
+    step_limit: Option<u64>,
+    //Time allowed and the moment it runs out
+    time_limit: Option<(Duration, Instant)>,
+    tracer: Option<Box<dyn Tracer<W>>>,
+    //Cell written by the instruction being recorded and its previous value
+    written: Option<(usize, W)>,
+    history: VecDeque<Undo<W>>,
+    history_limit: usize,
+    engine: Engine,
+    decoded: DecodeCache,
+}
+
+impl<W: Word> Computer<W> {
+    pub fn new(int_code: Vec<W>, input: Vec<W>) -> Self {
+        Self {
+            memory: Memory::new(int_code),
+            current_position: 0,
+            current_instruction: None,
+            relative_base: 0,
+            finished: false,
+            input: VecDeque::from(input),
+            outputs: Vec::new(),
+            steps: 0,
+            step_limit: None,
+            time_limit: None,
+            tracer: None,
+            written: None,
+            history: VecDeque::new(),
+            history_limit: 0,
+            engine: Engine::default(),
+            decoded: DecodeCache::default(),
+        }
+    }
+
+    pub fn from_snapshot(snapshot: Snapshot<W>) -> Self {
+        let mut computer = Computer::new(Vec::new(), Vec::new());
+        computer.memory = snapshot.memory;
+        computer.current_position = snapshot.position;
+        computer.relative_base = snapshot.relative_base;
+        computer.finished = snapshot.finished;
+        computer.input = VecDeque::from(snapshot.input);
+        computer.outputs = snapshot.outputs;
+        computer.steps = snapshot.steps;
+        computer
+    }
+
+    //Captures the machine state. Memory is shared, not copied.
+    pub fn snapshot(&self) -> Snapshot<W> {
+        Snapshot {
+            memory: self.memory.clone(),
+            position: self.current_position,
+            relative_base: self.relative_base,
+            finished: self.finished,
+            input: self.input.iter().cloned().collect(),
+            outputs: self.outputs.clone(),
+            steps: self.steps,
+        }
+    }
+
+    //Puts the machine back in the captured state. The step limit and the
+    //tracer are kept, the history is forgotten.
+    pub fn restore(&mut self, snapshot: &Snapshot<W>) {
+        self.history.clear();
+        self.decoded.clear();
+        self.memory = snapshot.memory.clone();
+        self.current_position = snapshot.position;
+        self.current_instruction = None;
+        self.relative_base = snapshot.relative_base;
+        self.finished = snapshot.finished;
+        self.input = snapshot.input.iter().cloned().collect();
+        self.outputs = snapshot.outputs.clone();
+        self.steps = snapshot.steps;
+    }
+
+    pub fn add_input(&mut self, value: W) {
+        self.input.push_back(value);
+    }
+
+    //Queues an input that may not fit in a narrower word
+    pub fn add_input_i64(&mut self, value: i64) -> Result<(), IntcodeError> {
+        let word = W::from_i64(value).ok_or(IntcodeError::InputOutOfRange { value })?;
+        self.add_input(word);
+        Ok(())
+    }
+
+    //Maximum number of memory cells the program may allocate
+    pub fn set_memory_limit(&mut self, cells: usize) {
+        self.memory.set_limit(cells);
+    }
+
+    //Maximum number of instructions the program may execute. Raising it
+    //after the limit was hit lets the program carry on.
+    pub fn set_step_limit(&mut self, limit: u64) {
+        self.step_limit = Some(limit);
+    }
+
+    //Wall-clock time the program may run for from now on. The clock is only
+    //looked at every few instructions, so it can overrun slightly. Setting it
+    //again after it ran out gives the program more time.
+    pub fn set_time_limit(&mut self, limit: Duration) {
+        self.time_limit = Some((limit, Instant::now() + limit));
+    }
+
+    pub fn clear_limits(&mut self) {
+        self.step_limit = None;
+        self.time_limit = None;
+    }
+
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+        self.decoded.clear();
+    }
+
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+
+    //Number of instructions executed so far
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    //Hands every instruction executed from now on to the tracer
+    pub fn set_tracer<T: Tracer<W>>(&mut self, tracer: T) {
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    //The installed tracer if it is of the given type
+    pub fn tracer<T: Tracer<W>>(&self) -> Option<&T> {
+        self.tracer
+            .as_ref()
+            .and_then(|tracer| tracer.as_any().downcast_ref())
+    }
+
+    //Flushes the tracer, for runs stopping before the program halts
+    pub fn flush_tracer(&mut self) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.flush();
+        }
+    }
+
+    //Removes the tracer, stopping the trace
+    pub fn take_tracer(&mut self) -> Option<Box<dyn Tracer<W>>> {
+        self.tracer.take()
+    }
+
+    //Remembers up to the given number of executed instructions so they can
+    //be stepped back. Zero turns the history off.
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+        while self.history.len() > limit {
+            self.history.pop_front();
+        }
+    }
+
+    //Number of instructions that can be stepped back
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    //Reverts the last executed instruction: position, relative base, the
+    //cell it overwrote, the input it read and the output it buffered. Outputs
+    //already drained cannot be taken back. Returns false when there is no
+    //history left.
+    pub fn step_back(&mut self) -> bool {
+        let undo = match self.history.pop_back() {
+            Some(undo) => undo,
+            None => return false,
+        };
+        if let Some((address, value)) = undo.write {
+            //The cell is allocated already, so this cannot hit the limit
+            self.store(address, value)
+                .expect("Undoing a write cannot allocate memory");
+        }
+        if let Some(value) = undo.input {
+            self.input.push_front(value);
+        }
+        if undo.output {
+            self.outputs.pop();
+        }
+        self.current_position = undo.position;
+        self.current_instruction = None;
+        self.relative_base = undo.relative_base;
+        self.finished = false;
+        self.steps -= 1;
+        true
+    }
+
+    //Steps back to the latest instruction that wrote the given cell, leaving
+    //it about to execute again. Returns false without changing anything when
+    //no remembered instruction wrote there.
+    pub fn rewind_to_write(&mut self, address: usize) -> bool {
+        let index = self.history.iter().rposition(|undo| match undo.write {
+            Some((written, _)) => written == address,
+            None => false,
+        });
+        match index {
+            Some(index) => {
+                while self.history.len() > index {
+                    self.step_back();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    //Address of the next instruction to execute
+    pub fn position(&self) -> usize {
+        self.current_position
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.finished
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    //Values queued for the program but not read yet
+    pub fn pending_input(&self) -> impl Iterator<Item = &W> {
+        self.input.iter()
+    }
+
+    pub fn read(&self, address: usize) -> W {
+        self.memory.read(address)
+    }
+
+    pub fn write(&mut self, address: usize, value: W) -> Result<(), IntcodeError> {
+        self.store(address, value)?;
+        Ok(())
+    }
+
+    //Writes a cell, dropping any instruction decoded from it
+    fn store(&mut self, address: usize, value: W) -> Result<(), MemoryError> {
+        self.decoded.invalidate(address);
+        self.memory.write(address, value)
+    }
+
+    //Every value output since the last drain, oldest first
+    pub fn outputs(&self) -> &[W] {
+        &self.outputs
+    }
+
+    pub fn last_output(&self) -> Option<&W> {
+        self.outputs.last()
+    }
+
+    //Takes the buffered outputs, leaving the buffer empty
+    pub fn drain_outputs(&mut self) -> std::vec::Drain<'_, W> {
+        self.outputs.drain(..)
+    }
+
+    pub fn step_limit(&self) -> Option<u64> {
+        self.step_limit
+    }
+
+    pub fn time_limit(&self) -> Option<Duration> {
+        self.time_limit.map(|(limit, _)| limit)
+    }
+
+    //Whether the time limit has run out, checking the clock right away
+    pub(crate) fn out_of_time(&self) -> bool {
+        self.time_limit
+            .is_some_and(|(_, deadline)| Instant::now() >= deadline)
+    }
+
+    //Hooks for compiled programs, which execute instructions themselves
+    pub(crate) fn set_position(&mut self, position: usize) {
+        self.current_position = position;
+    }
+
+    pub(crate) fn adjust_relative_base(&mut self, offset: i64) -> Result<(), IntcodeError> {
+        self.relative_base = self.relative(offset)?;
+        Ok(())
+    }
+
+    //Relative base plus an offset
+    fn relative(&self, offset: i64) -> Result<i64, IntcodeError> {
+        self.relative_base
+            .checked_add(offset)
+            .ok_or(IntcodeError::RelativeBaseOverflow {
+                address: self.current_position,
+            })
+    }
+
+    pub(crate) fn take_input(&mut self) -> Option<W> {
+        self.input.pop_front()
+    }
+
+    pub(crate) fn push_output(&mut self, value: W) {
+        self.outputs.push(value);
+    }
+
+    pub(crate) fn halt(&mut self) {
+        self.finished = true;
+    }
+
+    pub(crate) fn count_step(&mut self) {
+        self.steps += 1;
+    }
+
+    fn instruction(&self) -> Instruction {
+        self.current_instruction
+            .expect("No instruction has been decoded")
+    }
+
+    fn update_current_instruction(&mut self) -> Result<(), IntcodeError> {
+        let address = self.current_position;
+        if self.engine == Engine::Cached {
+            if let Some(instruction) = self.decoded.get(address) {
+                self.current_instruction = Some(instruction);
+                return Ok(());
+            }
+        }
+        let value = self.memory.read(address);
+        let opcode = value
+            .to_i64()
+            .ok_or(IntcodeError::ValueOutOfRange { address })?;
+        let instruction =
+            Instruction::new(opcode).map_err(|error| IntcodeError::decode(address, error))?;
+        if self.engine == Engine::Cached {
+            self.decoded.insert(address, instruction);
+        }
+        self.current_instruction = Some(instruction);
+        Ok(())
+    }
+
+    //Converts a word into a number usable for addressing
+    fn to_address(&self, value: &W) -> Result<i64, IntcodeError> {
+        value.to_i64().ok_or(IntcodeError::ValueOutOfRange {
+            address: self.current_position,
+        })
+    }
+
+    //Address referenced by the parameter at the given offset
+    fn address(&self, offset: usize) -> Result<usize, IntcodeError> {
+        let parameter = self.to_address(&self.memory.read(self.current_position + offset))?;
+        let address = match self.instruction().mode(offset) {
+            ParameterMode::Position => parameter,
+            ParameterMode::Relative => self.relative(parameter)?,
+            ParameterMode::Immediate => {
+                return Err(IntcodeError::WriteInImmediateMode {
+                    address: self.current_position,
+                })
+            }
+        };
+        if address < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                address: self.current_position,
+                target: address,
+            });
+        }
+        Ok(address as usize)
+    }
+
+    fn operand(&self, offset: usize) -> Result<W, IntcodeError> {
+        match self.instruction().mode(offset) {
+            ParameterMode::Immediate => Ok(self.memory.read(self.current_position + offset)),
+            _ => Ok(self.memory.read(self.address(offset)?)),
+        }
+    }
+
+    fn operand1(&self) -> Result<W, IntcodeError> {
+        self.operand(1)
+    }
+
+    fn operand2(&self) -> Result<W, IntcodeError> {
+        self.operand(2)
+    }
+
+    //Operand used as a jump target
+    fn target(&self) -> Result<usize, IntcodeError> {
+        let target = self.to_address(&self.operand2()?)?;
+        if target < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                address: self.current_position,
+                target,
+            });
+        }
+        Ok(target as usize)
+    }
+
+    fn set_result(&mut self, result: W, offset: usize) -> Result<(), IntcodeError> {
+        let result_position = self.address(offset)?;
+        if self.tracer.is_some() || self.history_limit > 0 {
+            self.written = Some((result_position, self.memory.read(result_position)));
+        }
+        self.store(result_position, result)?;
+        Ok(())
+    }
+
+    fn overflow_checked(&self, result: Option<W>) -> Result<W, IntcodeError> {
+        result.ok_or(IntcodeError::Overflow {
+            address: self.current_position,
+        })
+    }
+
+    //Values of the parameters the current instruction reads
+    fn read_operands(&self) -> Result<Vec<W>, IntcodeError> {
+        let operation = self.instruction().operation;
+        (1..=operation.parameters())
+            .filter(|offset| operation.written_parameter() != Some(*offset))
+            .map(|offset| self.operand(offset))
+            .collect()
+    }
+
+    fn execute_step(&mut self) -> Result<Option<State<W>>, IntcodeError> {
+        self.update_current_instruction()?;
+        if self.tracer.is_none() && self.history_limit == 0 {
+            return self.execute_instruction();
+        }
+        let address = self.current_position;
+        let relative_base = self.relative_base;
+        let operands = if self.tracer.is_some() {
+            self.read_operands()?
+        } else {
+            Vec::new()
+        };
+        let input = self.input.front().cloned();
+        self.written = None;
+        let state = self.execute_instruction()?;
+        if state == Some(State::AwaitingInput) {
+            return Ok(state);
+        }
+        let instruction = self.instruction();
+        let written = self.written.take();
+        if self.tracer.is_some() {
+            let event = TraceEvent {
+                step: self.steps,
+                address,
+                instruction,
+                operands,
+                write: written
+                    .as_ref()
+                    .map(|(written, _)| (*written, self.memory.read(*written))),
+                relative_base,
+            };
+            if let Some(tracer) = self.tracer.as_mut() {
+                tracer.record(&event);
+            }
+        }
+        if self.history_limit > 0 {
+            if self.history.len() == self.history_limit {
+                self.history.pop_front();
+            }
+            self.history.push_back(Undo {
+                position: address,
+                relative_base,
+                write: written,
+                input: input.filter(|_| instruction.operation == Operation::Save),
+                output: instruction.operation == Operation::Output,
+            });
+        }
+        Ok(state)
+    }
+
+    //Runs the instruction already decoded at the current position
+    fn execute_instruction(&mut self) -> Result<Option<State<W>>, IntcodeError> {
+        let mut step = 4;
+        match self.instruction().operation {
+            Operation::Add => {
+                //Calculate the result
+                let result = self.operand1()?.checked_add(&self.operand2()?);
+                self.set_result(self.overflow_checked(result)?, step - 1)?;
+                //Update current position
+                self.current_position += step;
+            }
+            Operation::Multiply => {
+                //Calculate the result
+                let result = self.operand1()?.checked_mul(&self.operand2()?);
+                self.set_result(self.overflow_checked(result)?, step - 1)?;
+                //Update current position
+                self.current_position += step;
+            }
+            Operation::Save => {
+                //Read input, leaving the machine untouched if there is none
+                if self.input.is_empty() {
+                    return Ok(Some(State::AwaitingInput));
+                }
+                self.address(1)?;
+                let value = self.input.pop_front().unwrap();
+                self.set_result(value, 1)?;
+                //Update current position
+                step = 2;
+                self.current_position += step;
+            }
+            Operation::Output => {
+                let value = self.operand1()?;
+                self.outputs.push(value.clone());
+                //Update current position
+                step = 2;
+                self.current_position += step;
+                return Ok(Some(State::Output(value)));
+            }
+            Operation::JumpIfTrue => {
+                //Update the current position
+                step = 3;
+                self.current_position = if !self.operand1()?.is_zero() {
+                    self.target()?
+                } else {
+                    self.current_position + step
+                }
+            }
+            Operation::JumpIfFalse => {
+                //Update the current position
+                step = 3;
+                self.current_position = if self.operand1()?.is_zero() {
+                    self.target()?
+                } else {
+                    self.current_position + step
+                }
+            }
+            Operation::LessThan => {
+                let result = truth(self.operand1()? < self.operand2()?);
+                self.set_result(result, step - 1)?;
+                //Update the current position
+                self.current_position += step;
+            }
+            Operation::Equals => {
+                let result = truth(self.operand1()? == self.operand2()?);
+                self.set_result(result, step - 1)?;
+                //Update the current position
+                self.current_position += step;
+            }
+            Operation::AdjustRelativeBase => {
+                let offset = self.to_address(&self.operand1()?)?;
+                self.adjust_relative_base(offset)?;
+                //Update the current position
+                step = 2;
+                self.current_position += step;
+            }
+            Operation::Halt => {
+                self.finished = true;
+                return Ok(Some(State::Halted));
+            }
+        }
+        Ok(None)
+    }
+
+    //Executes a single instruction. Returns the new state when the machine
+    //halts, waits for input or emits an output and None otherwise.
+    pub fn step(&mut self) -> Result<Option<State<W>>, IntcodeError> {
+        if self.finished {
+            return Ok(Some(State::Halted));
+        }
+        if let Some(limit) = self.step_limit {
+            if self.steps >= limit {
+                return Err(IntcodeError::StepLimitExceeded { limit });
+            }
+        }
+        if let Some((limit, _)) = self.time_limit {
+            if self.steps % CLOCK_INTERVAL == 0 && self.out_of_time() {
+                return Err(IntcodeError::TimeLimitExceeded { limit });
+            }
+        }
+        let state = self.execute_step()?;
+        if state != Some(State::AwaitingInput) {
+            self.steps += 1;
+        }
+        Ok(state)
+    }
+
+    //Runs until the program halts, needs input that has not been provided or
+    //emits an output. Can be called again to resume after any of them.
+    pub fn run(&mut self) -> Result<State<W>, IntcodeError> {
+        loop {
+            if let Some(state) = self.step()? {
+                return Ok(state);
+            }
+        }
+    }
+
+    //Runs wired to the given input and output until the program halts or the
+    //source has no more input for it. Outputs are delivered to the sink
+    //instead of being buffered, and a sink failing stops the program.
+    pub fn run_with<I, O>(
+        &mut self,
+        input: &mut I,
+        output: &mut O,
+    ) -> Result<State<W>, IntcodeError>
+    where
+        I: InputSource<W> + ?Sized,
+        O: OutputSink<W> + ?Sized,
+    {
+        loop {
+            match self.run()? {
+                State::Halted => return Ok(State::Halted),
+                State::AwaitingInput => match input.next_input() {
+                    Some(value) => self.add_input(value),
+                    None => return Ok(State::AwaitingInput),
+                },
+                State::Output(_) => {
+                    for value in self.outputs.drain(..) {
+                        output.send(value);
+                    }
+                    if output.failed() {
+                        return Err(IntcodeError::OutputFailed {
+                            address: self.current_position.saturating_sub(2),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    //Runs through every output until the program halts. Running out of input
+    //is an error here.
+    pub fn run_to_halt(&mut self) -> Result<(), IntcodeError> {
+        loop {
+            match self.run()? {
+                State::Halted => return Ok(()),
+                State::AwaitingInput => {
+                    return Err(IntcodeError::InputExhausted {
+                        address: self.current_position,
+                    })
+                }
+                State::Output(_) => (),
+            }
+        }
+    }
+}
+
+//Forks the machine. Memory is only copied once one of the two writes to it
+//and the tracer stays with the original.
+impl<W: Word> Clone for Computer<W> {
+    fn clone(&self) -> Self {
+        Self {
+            memory: self.memory.clone(),
+            current_position: self.current_position,
+            current_instruction: self.current_instruction,
+            relative_base: self.relative_base,
+            finished: self.finished,
+            input: self.input.clone(),
+            outputs: self.outputs.clone(),
+            steps: self.steps,
+            step_limit: self.step_limit,
+            time_limit: self.time_limit,
+            tracer: None,
+            written: None,
+            history: self.history.clone(),
+            history_limit: self.history_limit,
+            engine: self.engine,
+            decoded: self.decoded.clone(),
+        }
+    }
+}
+
+fn truth<W: Word>(condition: bool) -> W {
+    if condition {
+        W::one()
+    } else {
+        W::zero()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::io::{ChannelSink, ChannelSource, IterSource, WriterSink};
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    #[test]
+    fn add_and_multiply() {
+        let mut computer = Computer::new(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50], vec![]);
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.read(0), 3500);
+    }
+
+    #[test]
+    fn immediate_mode() {
+        let mut computer = Computer::new(vec![1002, 4, 3, 4, 33], vec![]);
+        computer.run_to_halt().unwrap();
+        assert!(computer.is_halted());
+    }
+
+    #[test]
+    fn input_is_echoed() {
+        let mut computer = Computer::new(vec![3, 0, 4, 0, 99], vec![42]);
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.outputs(), &[42]);
+    }
+
+    #[test]
+    fn relative_mode_read() {
+        let mut computer = Computer::new(vec![109, 5, 204, 1, 99, 0, 42], vec![]);
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.outputs(), &[42]);
+    }
+
+    #[test]
+    fn relative_mode_write() {
+        let mut computer = Computer::new(vec![109, 5, 109, 4, 203, 0, 204, 0, 99, 0], vec![13]);
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.outputs(), &[13]);
+    }
+
+    #[test]
+    fn large_numbers() {
+        let int_code: Vec<i64> = vec![104, 1125899906842624, 99];
+        let mut computer = Computer::new(int_code, vec![]);
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.outputs(), &[1125899906842624]);
+        let int_code: Vec<i64> = vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0];
+        let mut computer = Computer::new(int_code, vec![]);
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.last_output().unwrap().to_string().len(), 16);
+    }
+
+    #[test]
+    fn overflow_is_detected() {
+        let int_code: Vec<i32> = vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0];
+        let mut computer = Computer::new(int_code, vec![]);
+        assert_eq!(
+            computer.run_to_halt(),
+            Err(IntcodeError::Overflow { address: 0 })
+        );
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn big_integer_words() {
+        use num_bigint::BigInt;
+        let int_code: Vec<BigInt> = [
+            1102i64, 4294967296, 4294967296, 11, 1002, 11, 1000, 11, 4, 11, 99, 0,
+        ]
+        .iter()
+        .map(|value| BigInt::from(*value))
+        .collect();
+        let mut computer = Computer::new(int_code, vec![]);
+        computer.run_to_halt().unwrap();
+        assert_eq!(
+            computer.last_output().unwrap().to_string(),
+            "18446744073709551616000"
+        );
+    }
+
+    #[test]
+    fn memory_grows_past_the_image() {
+        let int_code = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let mut computer = Computer::new(int_code.clone(), vec![]);
+        computer.run_to_halt().unwrap();
+        //The program is a quine
+        assert_eq!(computer.outputs(), &int_code[..]);
+        assert_eq!(computer.memory.read(100), 16);
+    }
+
+    #[test]
+    fn memory_limit_stops_runaway_programs() {
+        //Writes increasingly far addresses until the limit is hit
+        let int_code = vec![21101, 0, 0, 100, 109, 1, 1105, 1, 0];
+        let mut computer = Computer::new(int_code, vec![]);
+        computer.set_memory_limit(1000);
+        match computer.run_to_halt() {
+            Err(IntcodeError::Memory(_)) => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_opcode_reports_its_address() {
+        let mut computer = Computer::new(vec![1101, 1, 1, 0, 42], vec![]);
+        assert_eq!(
+            computer.run_to_halt(),
+            Err(IntcodeError::UnknownOpcode {
+                address: 4,
+                opcode: 42
+            })
+        );
+        let mut computer = Computer::new(vec![301, 0, 0, 0, 99], vec![]);
+        assert_eq!(
+            computer.run_to_halt(),
+            Err(IntcodeError::InvalidMode {
+                address: 0,
+                mode: 3
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_addresses_are_errors() {
+        let mut computer = Computer::new(vec![11101, 1, 1, 0, 99], vec![]);
+        assert_eq!(
+            computer.run_to_halt(),
+            Err(IntcodeError::WriteInImmediateMode { address: 0 })
+        );
+        let mut computer = Computer::new(vec![204, -3, 99], vec![]);
+        assert_eq!(
+            computer.run_to_halt(),
+            Err(IntcodeError::NegativeAddress {
+                address: 0,
+                target: -3
+            })
+        );
+    }
+
+    #[test]
+    fn relative_base_overflow_is_an_error() {
+        let mut computer = Computer::new(vec![109, i64::MAX, 109, 1, 99], vec![]);
+        assert_eq!(
+            computer.run_to_halt(),
+            Err(IntcodeError::RelativeBaseOverflow { address: 2 })
+        );
+        assert_eq!(computer.relative_base(), i64::MAX);
+        let mut computer = Computer::new(vec![109, i64::MAX, 204, 1, 99], vec![]);
+        assert_eq!(
+            computer.run_to_halt(),
+            Err(IntcodeError::RelativeBaseOverflow { address: 2 })
+        );
+    }
+
+    #[test]
+    fn inputs_too_large_for_the_word_are_rejected() {
+        let mut computer: Computer<i32> = Computer::new(vec![3, 5, 4, 5, 99, 0], vec![]);
+        assert_eq!(
+            computer.add_input_i64(i64::MAX),
+            Err(IntcodeError::InputOutOfRange { value: i64::MAX })
+        );
+        assert_eq!(
+            computer.run_to_halt(),
+            Err(IntcodeError::InputExhausted { address: 0 })
+        );
+        computer.add_input_i64(-7).unwrap();
+        assert_eq!(computer.run_to_halt(), Ok(()));
+        assert_eq!(computer.outputs(), &[-7]);
+    }
+
+    #[test]
+    fn missing_input_does_not_consume_the_instruction() {
+        let mut computer = Computer::new(vec![3, 5, 4, 5, 99, 0], vec![]);
+        assert_eq!(
+            computer.run_to_halt(),
+            Err(IntcodeError::InputExhausted { address: 0 })
+        );
+        computer.add_input(7);
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.outputs(), &[7]);
+    }
+
+    #[test]
+    fn run_suspends_on_input_and_output() {
+        let mut computer = Computer::new(vec![3, 9, 4, 9, 3, 9, 4, 9, 99, 0], vec![]);
+        assert_eq!(computer.run(), Ok(State::AwaitingInput));
+        assert_eq!(computer.run(), Ok(State::AwaitingInput));
+        computer.add_input(1);
+        assert_eq!(computer.run(), Ok(State::Output(1)));
+        assert_eq!(computer.run(), Ok(State::AwaitingInput));
+        computer.add_input(2);
+        assert_eq!(computer.run(), Ok(State::Output(2)));
+        assert_eq!(computer.run(), Ok(State::Halted));
+        assert_eq!(computer.run(), Ok(State::Halted));
+        assert_eq!(computer.steps(), 5);
+    }
+
+    #[test]
+    fn step_limit_stops_infinite_loops() {
+        let mut computer = Computer::new(vec![1105, 1, 0], vec![]);
+        computer.set_step_limit(100);
+        assert_eq!(
+            computer.run_to_halt(),
+            Err(IntcodeError::StepLimitExceeded { limit: 100 })
+        );
+        assert_eq!(computer.steps(), 100);
+    }
+
+    #[test]
+    fn raising_the_step_limit_resumes() {
+        //Counts down from 100 and prints zero
+        let mut computer = Computer::new(vec![1001, 9, -1, 9, 1005, 9, 0, 104, 0, 100, 99], vec![]);
+        computer.set_step_limit(50);
+        let error = computer.run().unwrap_err();
+        assert!(error.is_budget_exceeded());
+        assert_eq!(computer.steps(), 50);
+        computer.set_step_limit(1000);
+        assert_eq!(computer.run(), Ok(State::Output(0)));
+        assert_eq!(computer.steps(), 201);
+    }
+
+    #[test]
+    fn time_limit_stops_infinite_loops() {
+        let mut computer = Computer::new(vec![1105, 1, 0], vec![]);
+        computer.set_time_limit(Duration::from_millis(20));
+        let limit = Duration::from_millis(20);
+        assert_eq!(
+            computer.run(),
+            Err(IntcodeError::TimeLimitExceeded { limit })
+        );
+        assert!(computer.steps() > 0);
+        assert!(computer.steps() % CLOCK_INTERVAL == 0);
+
+        let mut computer = Computer::new(vec![104, 7, 99], vec![]);
+        computer.set_time_limit(Duration::from_secs(0));
+        assert!(computer.run().unwrap_err().is_budget_exceeded());
+        computer.set_time_limit(Duration::from_secs(60));
+        assert_eq!(computer.run(), Ok(State::Output(7)));
+        computer.clear_limits();
+        assert_eq!(computer.time_limit(), None);
+        assert_eq!(computer.run(), Ok(State::Halted));
+    }
+
+    #[test]
+    fn drain_outputs_empties_the_buffer() {
+        let mut computer = Computer::new(vec![104, 1, 104, 2, 104, 3, 99], vec![]);
+        assert_eq!(computer.run(), Ok(State::Output(1)));
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.drain_outputs().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert!(computer.outputs().is_empty());
+    }
+
+    #[test]
+    fn run_with_pulls_input_and_pushes_output() {
+        let int_code = vec![3, 11, 1002, 11, 2, 11, 4, 11, 1105, 1, 0, 0];
+        let mut computer = Computer::new(int_code, vec![]);
+        let mut input = IterSource(vec![1, 2, 3].into_iter());
+        let mut output = Vec::new();
+        let state = computer.run_with(&mut input, &mut output).unwrap();
+        assert_eq!(state, State::AwaitingInput);
+        assert_eq!(output, vec![2, 4, 6]);
+        assert!(computer.outputs().is_empty());
+    }
+
+    #[test]
+    fn machines_connected_through_channels() {
+        //Doubles every input value
+        let int_code = vec![3, 11, 1002, 11, 2, 11, 4, 11, 1105, 1, 0, 0];
+        let (first_input, first_receiver) = channel();
+        let (link, second_receiver) = channel();
+        let (second_output, results) = channel();
+        let first = Computer::new(int_code.clone(), vec![]);
+        let second = Computer::new(int_code, vec![]);
+        let handles = vec![
+            thread::spawn(move || {
+                let mut first = first;
+                let mut sink = ChannelSink::new(link);
+                first.run_with(&mut ChannelSource(first_receiver), &mut sink)
+            }),
+            thread::spawn(move || {
+                let mut second = second;
+                let mut sink = ChannelSink::new(second_output);
+                second.run_with(&mut ChannelSource(second_receiver), &mut sink)
+            }),
+        ];
+        for value in 1..=3 {
+            first_input.send(value).unwrap();
+        }
+        drop(first_input);
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), Ok(State::AwaitingInput));
+        }
+        assert_eq!(results.iter().collect::<Vec<_>>(), vec![4, 8, 12]);
+    }
+
+    #[test]
+    fn failing_sinks_stop_the_machine() {
+        //Outputs 1 forever
+        let int_code = vec![104, 1, 1105, 1, 0];
+        let (sender, receiver) = channel();
+        drop(receiver);
+        let mut computer = Computer::new(int_code.clone(), vec![]);
+        let mut sink = ChannelSink::new(sender);
+        assert_eq!(
+            computer.run_with(&mut VecDeque::new(), &mut sink),
+            Err(IntcodeError::OutputFailed { address: 0 })
+        );
+        assert_eq!(computer.steps(), 1);
+        let mut buffer = [0u8; 5];
+        let mut computer = Computer::new(int_code, vec![]);
+        let mut sink = WriterSink::new(&mut buffer[..]);
+        assert_eq!(
+            computer.run_with(&mut VecDeque::new(), &mut sink),
+            Err(IntcodeError::OutputFailed { address: 0 })
+        );
+        assert_eq!(computer.steps(), 5);
+    }
+
+    #[test]
+    fn step_back_reverts_memory_input_and_output() {
+        let mut computer = Computer::new(vec![3, 9, 1001, 9, 1, 9, 4, 9, 99, 0], vec![41]);
+        computer.set_history_limit(10);
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.outputs(), &[42]);
+        assert_eq!(computer.history_len(), 4);
+        assert!(computer.step_back());
+        assert!(!computer.is_halted());
+        assert!(computer.step_back());
+        assert_eq!(computer.position(), 6);
+        assert!(computer.outputs().is_empty());
+        assert!(computer.step_back());
+        assert_eq!(computer.read(9), 41);
+        assert!(computer.step_back());
+        assert_eq!(computer.read(9), 0);
+        assert_eq!(computer.pending_input().collect::<Vec<_>>(), vec![&41]);
+        assert_eq!((computer.position(), computer.steps()), (0, 0));
+        assert!(!computer.step_back());
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.outputs(), &[42]);
+    }
+
+    #[test]
+    fn rewind_to_self_modifying_write() {
+        //The first instruction turns the add at 4 into a halt
+        let mut computer = Computer::new(vec![1101, 50, 49, 4, 1, 0, 0, 0], vec![]);
+        computer.set_history_limit(100);
+        computer.run_to_halt().unwrap();
+        assert!(!computer.rewind_to_write(7));
+        assert!(computer.rewind_to_write(4));
+        assert_eq!(computer.position(), 0);
+        assert_eq!(computer.read(4), 1);
+        assert_eq!(computer.history_len(), 0);
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut computer = Computer::new(vec![1101, 0, 0, 9, 1105, 1, 0, 99, 0, 0], vec![]);
+        computer.set_history_limit(3);
+        computer.set_step_limit(100);
+        assert!(computer.run().is_err());
+        assert_eq!(computer.history_len(), 3);
+        assert!(computer.step_back() && computer.step_back() && computer.step_back());
+        assert!(!computer.step_back());
+        assert_eq!(computer.steps(), 97);
+    }
+
+    #[test]
+    fn compare_with_eight() {
+        let int_code = vec![3, 3, 1108, -1, 8, 3, 4, 3, 99];
+        let mut computer = Computer::new(int_code.clone(), vec![8]);
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.outputs(), &[1]);
+        let mut computer = Computer::new(int_code, vec![7]);
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.outputs(), &[0]);
+    }
+}
@@ -0,0 +1,510 @@
+use super::computer::{Computer, State};
+use super::disassembler::decode;
+use super::error::IntcodeError;
+use super::word::Word;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead, Write};
+
+//Instructions remembered for stepping back
+const HISTORY: usize = 100_000;
+
+//Cells shown by a single dump, memory being unbounded
+const DUMP_LIMIT: usize = 4096;
+
+const HELP: &str = "\
+step [n]            execute n instructions, stepping over outputs (s)
+continue            run until a breakpoint, watchpoint, input or halt (c)
+until-output        continue, also stopping after the next output (o)
+back [n]            undo the last n instructions (bs)
+rewind <addr>       go back to the last instruction that wrote addr
+break <addr>        stop before executing the instruction at addr (b)
+delete <addr>       remove a breakpoint (d)
+watch <addr>        stop when the cell at addr changes (w)
+unwatch <addr>      remove a watchpoint
+regs                show registers and the next instruction (r)
+mem <addr> [len]    dump memory (m)
+disas [addr] [n]    disassemble n instructions, from pc by default (x)
+set <addr> <value>  write a memory cell
+input <values...>   queue input values (i)
+outputs             show the outputs produced so far
+help                show this text (h)
+quit                leave the debugger (q)";
+
+//Why the debugger gave control back
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stop<W> {
+    //Requested number of steps executed
+    Stepped,
+    Breakpoint(usize),
+    Watchpoint { address: usize, old: W, new: W },
+    Output(W),
+    AwaitingInput,
+    Halted,
+}
+
+pub struct Debugger<W: Word = i64> {
+    computer: Computer<W>,
+    breakpoints: BTreeSet<usize>,
+    //Last value seen in every watched cell
+    watchpoints: BTreeMap<usize, W>,
+}
+
+impl<W: Word> Debugger<W> {
+    pub fn new(mut computer: Computer<W>) -> Self {
+        computer.set_history_limit(HISTORY);
+        Debugger {
+            computer,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+        }
+    }
+
+    pub fn computer(&self) -> &Computer<W> {
+        &self.computer
+    }
+
+    pub fn computer_mut(&mut self) -> &mut Computer<W> {
+        &mut self.computer
+    }
+
+    pub fn add_breakpoint(&mut self, address: usize) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn add_watchpoint(&mut self, address: usize) {
+        let value = self.computer.read(address);
+        self.watchpoints.insert(address, value);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: usize) -> bool {
+        self.watchpoints.remove(&address).is_some()
+    }
+
+    //Executes one instruction, reporting anything worth stopping for
+    fn execute(&mut self) -> Result<Option<Stop<W>>, IntcodeError> {
+        let state = self.computer.step()?;
+        for (address, old) in self.watchpoints.iter_mut() {
+            let new = self.computer.read(*address);
+            if new != *old {
+                let old = std::mem::replace(old, new.clone());
+                return Ok(Some(Stop::Watchpoint {
+                    address: *address,
+                    old,
+                    new,
+                }));
+            }
+        }
+        Ok(match state {
+            Some(State::Halted) => Some(Stop::Halted),
+            Some(State::AwaitingInput) => Some(Stop::AwaitingInput),
+            Some(State::Output(value)) => Some(Stop::Output(value)),
+            None => None,
+        })
+    }
+
+    //Executes up to the given number of instructions, stopping at
+    //breakpoints after the first one
+    pub fn step(&mut self, count: usize) -> Result<Stop<W>, IntcodeError> {
+        for index in 0..count {
+            let position = self.computer.position();
+            if index > 0 && self.breakpoints.contains(&position) {
+                return Ok(Stop::Breakpoint(position));
+            }
+            match self.execute()? {
+                Some(Stop::Output(_)) | None => (),
+                Some(stop) => return Ok(stop),
+            }
+        }
+        Ok(Stop::Stepped)
+    }
+
+    //Runs until something stops the machine. Outputs stop it only when asked.
+    fn run(&mut self, stop_on_output: bool) -> Result<Stop<W>, IntcodeError> {
+        let mut first = true;
+        loop {
+            let position = self.computer.position();
+            if !first && self.breakpoints.contains(&position) {
+                return Ok(Stop::Breakpoint(position));
+            }
+            first = false;
+            match self.execute()? {
+                Some(Stop::Output(_)) if !stop_on_output => (),
+                Some(stop) => return Ok(stop),
+                None => (),
+            }
+        }
+    }
+
+    //Runs until a breakpoint, a watchpoint, missing input or halt
+    pub fn cont(&mut self) -> Result<Stop<W>, IntcodeError> {
+        self.run(false)
+    }
+
+    //Like cont, but also stops after the next output
+    pub fn until_output(&mut self) -> Result<Stop<W>, IntcodeError> {
+        self.run(true)
+    }
+
+    //Undoes up to the given number of instructions, returning how many were
+    pub fn step_back(&mut self, count: usize) -> usize {
+        let undone = (0..count).take_while(|_| self.computer.step_back()).count();
+        self.sync_watchpoints();
+        undone
+    }
+
+    //Goes back to the latest instruction that wrote the cell
+    pub fn rewind_to_write(&mut self, address: usize) -> bool {
+        let found = self.computer.rewind_to_write(address);
+        self.sync_watchpoints();
+        found
+    }
+
+    fn sync_watchpoints(&mut self) {
+        for (address, value) in self.watchpoints.iter_mut() {
+            *value = self.computer.read(*address);
+        }
+    }
+
+    //Registers and the instruction about to be executed
+    pub fn registers(&self) -> String {
+        let position = self.computer.position();
+        let next = match decode(|address| self.computer.read(address), position) {
+            Some(decoded) => decoded.to_string(),
+            None => format!("data {}", self.computer.read(position)),
+        };
+        format!(
+            "pc={} rb={} steps={}{}\n{:>6}  {}",
+            position,
+            self.computer.relative_base(),
+            self.computer.steps(),
+            if self.computer.is_halted() {
+                " halted"
+            } else {
+                ""
+            },
+            position,
+            next
+        )
+    }
+
+    //Memory cells in rows of eight, at most DUMP_LIMIT of them
+    pub fn dump(&self, start: usize, len: usize) -> String {
+        let end = start.saturating_add(len.min(DUMP_LIMIT));
+        (start..end)
+            .step_by(8)
+            .map(|row| {
+                let values: Vec<_> = (row..row.saturating_add(8).min(end))
+                    .map(|address| self.computer.read(address).to_string())
+                    .collect();
+                format!("{:>6}  {}", row, values.join(" "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    //Disassembles the given number of instructions from an address
+    pub fn disassemble(&self, start: usize, count: usize) -> String {
+        let mut lines = Vec::new();
+        let mut address = start;
+        for _ in 0..count {
+            //Instructions cannot reach past the last address
+            if address.checked_add(4).is_none() {
+                break;
+            }
+            let marker = if address == self.computer.position() {
+                ">"
+            } else {
+                " "
+            };
+            match decode(|address| self.computer.read(address), address) {
+                Some(decoded) => {
+                    lines.push(format!("{}{:>5}  {}", marker, address, decoded));
+                    address = decoded.next();
+                }
+                None => {
+                    let value = self.computer.read(address);
+                    lines.push(format!("{}{:>5}  data {}", marker, address, value));
+                    address += 1;
+                }
+            }
+        }
+        lines.join("\n")
+    }
+
+    //Runs a single command line and returns the text to show, or None to quit
+    pub fn command(&mut self, line: &str) -> Option<String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (name, arguments) = match words.split_first() {
+            Some((name, arguments)) => (*name, arguments),
+            None => return Some(String::new()),
+        };
+        let result = match name {
+            "s" | "step" => parse_or(arguments.first(), 1)
+                .and_then(|count| self.report(|debugger| debugger.step(count))),
+            "c" | "continue" => self.report(Debugger::cont),
+            "o" | "until-output" => self.report(Debugger::until_output),
+            "bs" | "back" => parse_or(arguments.first(), 1).map(|count| {
+                let undone = self.step_back(count);
+                format!("Undid {} instructions\n{}", undone, self.registers())
+            }),
+            "rewind" => parse_address(arguments.first()).map(|address| {
+                if self.rewind_to_write(address) {
+                    format!("Last write to {}\n{}", address, self.registers())
+                } else {
+                    format!("No remembered write to {}", address)
+                }
+            }),
+            "b" | "break" => parse_address(arguments.first()).map(|address| {
+                self.add_breakpoint(address);
+                format!("Breakpoint at {}", address)
+            }),
+            "d" | "delete" => parse_address(arguments.first()).map(|address| {
+                if self.remove_breakpoint(address) {
+                    format!("Removed breakpoint at {}", address)
+                } else {
+                    format!("No breakpoint at {}", address)
+                }
+            }),
+            "w" | "watch" => parse_address(arguments.first()).map(|address| {
+                self.add_watchpoint(address);
+                format!("Watching {}", address)
+            }),
+            "unwatch" => parse_address(arguments.first()).map(|address| {
+                if self.remove_watchpoint(address) {
+                    format!("Stopped watching {}", address)
+                } else {
+                    format!("Not watching {}", address)
+                }
+            }),
+            "r" | "regs" => Ok(self.registers()),
+            "m" | "mem" => parse_address(arguments.first())
+                .and_then(|start| parse_or(arguments.get(1), 8).map(|len| self.dump(start, len))),
+            "x" | "disas" => {
+                parse_or(arguments.first(), self.computer.position()).and_then(|start| {
+                    parse_or(arguments.get(1), 8).map(|count| self.disassemble(start, count))
+                })
+            }
+            "set" => parse_address(arguments.first()).and_then(|address| {
+                parse_value::<W>(arguments.get(1)).and_then(|value| {
+                    self.computer
+                        .write(address, value)
+                        .map(|_| format!("{} = {}", address, self.computer.read(address)))
+                        .map_err(|error| error.to_string())
+                })
+            }),
+            "i" | "input" => arguments
+                .iter()
+                .map(|argument| parse_value::<W>(Some(argument)))
+                .collect::<Result<Vec<_>, _>>()
+                .map(|values| {
+                    let count = values.len();
+                    values
+                        .into_iter()
+                        .for_each(|value| self.computer.add_input(value));
+                    format!("Queued {} input values", count)
+                }),
+            "outputs" => Ok(self
+                .computer
+                .outputs()
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join(",")),
+            "h" | "help" => Ok(HELP.to_string()),
+            "q" | "quit" => return None,
+            _ => Err(format!("Unknown command '{}', try help", name)),
+        };
+        Some(result.unwrap_or_else(|error| format!("Error: {}", error)))
+    }
+
+    fn report<F>(&mut self, action: F) -> Result<String, String>
+    where
+        F: FnOnce(&mut Self) -> Result<Stop<W>, IntcodeError>,
+    {
+        let stop = action(self).map_err(|error| error.to_string())?;
+        let reason = match stop {
+            Stop::Stepped => String::new(),
+            Stop::Breakpoint(address) => format!("Breakpoint at {}\n", address),
+            Stop::Watchpoint { address, old, new } => {
+                format!("Watchpoint {} changed from {} to {}\n", address, old, new)
+            }
+            Stop::Output(value) => format!("Output {}\n", value),
+            Stop::AwaitingInput => "Waiting for input\n".to_string(),
+            Stop::Halted => "Halted\n".to_string(),
+        };
+        Ok(format!("{}{}", reason, self.registers()))
+    }
+
+    //Reads commands until quit or the end of the input
+    pub fn repl<R: BufRead, O: Write>(&mut self, input: R, mut output: O) -> io::Result<()> {
+        write!(output, "(intcode) ")?;
+        output.flush()?;
+        for line in input.lines() {
+            match self.command(&line?) {
+                Some(text) => {
+                    if !text.is_empty() {
+                        writeln!(output, "{}", text)?;
+                    }
+                }
+                None => break,
+            }
+            write!(output, "(intcode) ")?;
+            output.flush()?;
+        }
+        writeln!(output)
+    }
+}
+
+fn parse_address(argument: Option<&&str>) -> Result<usize, String> {
+    let argument = argument.ok_or_else(|| "Missing address".to_string())?;
+    argument
+        .parse()
+        .map_err(|_| format!("Invalid address '{}'", argument))
+}
+
+fn parse_or(argument: Option<&&str>, default: usize) -> Result<usize, String> {
+    match argument {
+        Some(_) => parse_address(argument),
+        None => Ok(default),
+    }
+}
+
+fn parse_value<W: Word>(argument: Option<&&str>) -> Result<W, String> {
+    let argument = argument.ok_or_else(|| "Missing value".to_string())?;
+    argument
+        .parse()
+        .map_err(|_| format!("Invalid value '{}'", argument))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    //Reads a value and counts down from it, printing every number
+    fn countdown() -> Computer {
+        Computer::new(
+            vec![3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0],
+            vec![],
+        )
+    }
+
+    #[test]
+    fn breakpoints_stop_before_the_instruction() {
+        let mut debugger = Debugger::new(countdown());
+        debugger.computer_mut().add_input(3);
+        debugger.add_breakpoint(4);
+        assert_eq!(debugger.cont(), Ok(Stop::Breakpoint(4)));
+        assert_eq!(debugger.computer().outputs(), &[3]);
+        //Continuing from a breakpoint leaves it first
+        assert_eq!(debugger.cont(), Ok(Stop::Breakpoint(4)));
+        assert_eq!(debugger.computer().outputs(), &[3, 2]);
+        debugger.remove_breakpoint(4);
+        assert_eq!(debugger.cont(), Ok(Stop::Halted));
+        assert_eq!(debugger.computer().outputs(), &[3, 2, 1]);
+    }
+
+    #[test]
+    fn watchpoints_report_changes() {
+        let mut debugger = Debugger::new(countdown());
+        debugger.computer_mut().add_input(2);
+        debugger.add_watchpoint(12);
+        assert_eq!(
+            debugger.cont(),
+            Ok(Stop::Watchpoint {
+                address: 12,
+                old: 0,
+                new: 2
+            })
+        );
+        assert_eq!(debugger.until_output(), Ok(Stop::Output(2)));
+        assert_eq!(
+            debugger.cont(),
+            Ok(Stop::Watchpoint {
+                address: 12,
+                old: 2,
+                new: 1
+            })
+        );
+    }
+
+    #[test]
+    fn stepping_and_missing_input() {
+        let mut debugger = Debugger::new(countdown());
+        assert_eq!(debugger.step(3), Ok(Stop::AwaitingInput));
+        debugger.computer_mut().add_input(1);
+        assert_eq!(debugger.step(2), Ok(Stop::Stepped));
+        assert_eq!(debugger.computer().position(), 4);
+        //Steps stop at breakpoints, but not at the one they start on
+        debugger.add_breakpoint(4);
+        debugger.add_breakpoint(8);
+        assert_eq!(debugger.step(10), Ok(Stop::Breakpoint(8)));
+        assert_eq!(debugger.step(10), Ok(Stop::Halted));
+        assert_eq!(debugger.computer().outputs(), &[1]);
+    }
+
+    #[test]
+    fn dumps_stay_in_bounds() {
+        let debugger = Debugger::new(countdown());
+        assert_eq!(
+            debugger.dump(usize::MAX - 1, 5),
+            format!("{:>6}  0", usize::MAX - 1)
+        );
+        assert_eq!(debugger.dump(0, usize::MAX).lines().count(), DUMP_LIMIT / 8);
+        assert_eq!(debugger.disassemble(usize::MAX, 3), "");
+    }
+
+    #[test]
+    fn repl_session() {
+        let mut debugger = Debugger::new(countdown());
+        let script = "input 2\nbreak 11\nc\nmem 10 3\nx 8 2\nbogus\nq\nstep\n";
+        let mut output = Vec::new();
+        debugger.repl(Cursor::new(script), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Queued 1 input values\n"));
+        assert!(output.contains("Breakpoint at 11\npc=11 rb=0 steps=7\n    11  hlt\n"));
+        assert!(output.contains("    10  2 99 0\n"));
+        assert!(output.contains("     8  jt [12], 2\n>   11  hlt\n"));
+        assert!(output.contains("Error: Unknown command 'bogus', try help\n"));
+        //Nothing runs after quitting
+        assert_eq!(debugger.computer().steps(), 7);
+    }
+
+    #[test]
+    fn stepping_back_and_rewinding() {
+        let mut debugger = Debugger::new(countdown());
+        debugger.computer_mut().add_input(2);
+        assert_eq!(debugger.cont(), Ok(Stop::Halted));
+        assert_eq!(debugger.step_back(2), 2);
+        assert_eq!(debugger.computer().position(), 8);
+        assert_eq!(
+            debugger.command("rewind 12").unwrap(),
+            "Last write to 12\npc=4 rb=0 steps=5\n     4  add [12], -1, [12]"
+        );
+        debugger.add_watchpoint(12);
+        assert_eq!(
+            debugger.step(1),
+            Ok(Stop::Watchpoint {
+                address: 12,
+                old: 1,
+                new: 0
+            })
+        );
+        assert_eq!(
+            debugger.command("back 100").unwrap().lines().next(),
+            Some("Undid 6 instructions")
+        );
+        assert_eq!(
+            debugger.computer().pending_input().collect::<Vec<_>>(),
+            vec![&2]
+        );
+        assert_eq!(
+            debugger.command("rewind 3").unwrap(),
+            "No remembered write to 3"
+        );
+    }
+}
@@ -0,0 +1,332 @@
+use super::instruction::{Instruction, Operation, ParameterMode};
+use super::word::Word;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+//Consecutive data words printed on a single line
+const DATA_PER_LINE: usize = 8;
+
+//Parameter of a decoded instruction together with its mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operand<W> {
+    pub mode: ParameterMode,
+    pub value: W,
+}
+
+impl<W: Word> fmt::Display for Operand<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mode {
+            ParameterMode::Position => write!(f, "[{}]", self.value),
+            ParameterMode::Immediate => write!(f, "{}", self.value),
+            ParameterMode::Relative => match self.value.to_i64() {
+                Some(offset) if offset < 0 => write!(f, "[rb-{}]", -(offset as i128)),
+                _ => write!(f, "[rb+{}]", self.value),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decoded<W> {
+    pub address: usize,
+    pub instruction: Instruction,
+    pub operands: Vec<Operand<W>>,
+}
+
+impl<W: Word> Decoded<W> {
+    //Number of words taken by the instruction
+    pub fn size(&self) -> usize {
+        1 + self.operands.len()
+    }
+
+    //Address of the next instruction when execution does not jump
+    pub fn next(&self) -> usize {
+        self.address + self.size()
+    }
+
+    //Target of a jump known without running the program
+    pub fn jump_target(&self) -> Option<usize> {
+        match self.instruction.operation {
+            Operation::JumpIfTrue | Operation::JumpIfFalse => {
+                let target = &self.operands[1];
+                match target.mode {
+                    ParameterMode::Immediate => match target.value.to_i64() {
+                        Some(target) if target >= 0 => Some(target as usize),
+                        _ => None,
+                    },
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    //Whether execution can continue with the next instruction
+    pub fn falls_through(&self) -> bool {
+        self.instruction.operation != Operation::Halt
+    }
+
+    fn write_with_labels(
+        &self,
+        f: &mut fmt::Formatter,
+        labels: &BTreeMap<usize, String>,
+    ) -> fmt::Result {
+        write!(f, "{}", self.instruction.operation.mnemonic())?;
+        for (index, operand) in self.operands.iter().enumerate() {
+            write!(f, "{}", if index == 0 { " " } else { ", " })?;
+            let label = match (index, self.jump_target()) {
+                (1, Some(target)) => labels.get(&target),
+                _ => None,
+            };
+            match label {
+                Some(label) => write!(f, "{}", label)?,
+                None => write!(f, "{}", operand)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Word> fmt::Display for Decoded<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_with_labels(f, &BTreeMap::new())
+    }
+}
+
+//Decodes the instruction at the given address, None if it is not valid code
+pub fn decode<W: Word, F: Fn(usize) -> W>(read: F, address: usize) -> Option<Decoded<W>> {
+    let instruction = Instruction::new(read(address).to_i64()?).ok()?;
+    let operands = (1..=instruction.operation.parameters())
+        .map(|offset| Operand {
+            mode: instruction.mode(offset),
+            value: read(address + offset),
+        })
+        .collect();
+    Some(Decoded {
+        address,
+        instruction,
+        operands,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Line<W> {
+    Code(Decoded<W>),
+    Data { address: usize, values: Vec<W> },
+}
+
+impl<W> Line<W> {
+    pub fn address(&self) -> usize {
+        match self {
+            Line::Code(decoded) => decoded.address,
+            Line::Data { address, .. } => *address,
+        }
+    }
+}
+
+//Annotated listing of a whole memory image
+#[derive(Debug, Clone)]
+pub struct Listing<W> {
+    pub lines: Vec<Line<W>>,
+    pub labels: BTreeMap<usize, String>,
+    //Instructions found by following the control flow from address 0. The
+    //rest of the code was guessed by decoding the words left over.
+    pub reachable: BTreeSet<usize>,
+}
+
+impl<W: Word> fmt::Display for Listing<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            if let Some(label) = self.labels.get(&line.address()) {
+                writeln!(f, "{}:", label)?;
+            }
+            write!(f, "{:>6}  ", line.address())?;
+            match line {
+                Line::Code(decoded) => {
+                    decoded.write_with_labels(f, &self.labels)?;
+                    if !self.reachable.contains(&decoded.address) {
+                        write!(f, "  ; unreached")?;
+                    }
+                }
+                Line::Data { values, .. } => {
+                    let values: Vec<_> = values.iter().map(|value| value.to_string()).collect();
+                    write!(f, "data {}", values.join(", "))?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+//Decodes the instruction at the given address if it fits in the image.
+//Instructions writing to an immediate parameter fail when run. They are left
+//as data like any other invalid word.
+pub(super) fn decode_in<W: Word>(image: &[W], address: usize) -> Option<Decoded<W>> {
+    decode(
+        |address| image.get(address).cloned().unwrap_or_else(W::zero),
+        address,
+    )
+    .filter(|decoded| decoded.next() <= image.len())
+    .filter(|decoded| {
+        let instruction = decoded.instruction;
+        instruction
+            .operation
+            .written_parameter()
+            .map_or(true, |offset| {
+                instruction.mode(offset) != ParameterMode::Immediate
+            })
+    })
+}
+
+//Turns a memory image into a listing. Code is found by following the control
+//flow from address 0; words that are never reached are decoded as code when
+//they look like valid instructions and shown as data otherwise.
+pub fn disassemble<W: Word>(image: &[W]) -> Listing<W> {
+    let mut code: BTreeMap<usize, Decoded<W>> = BTreeMap::new();
+    let mut covered = vec![false; image.len()];
+    let mut pending = vec![0];
+    while let Some(address) = pending.pop() {
+        if address >= image.len() || covered[address] {
+            continue;
+        }
+        let decoded = match decode_in(image, address) {
+            Some(decoded) => decoded,
+            None => continue,
+        };
+        //Instructions overlapping already decoded code are left as data
+        if covered[address..decoded.next()].iter().any(|word| *word) {
+            continue;
+        }
+        covered[address..decoded.next()]
+            .iter_mut()
+            .for_each(|word| *word = true);
+        if decoded.falls_through() {
+            pending.push(decoded.next());
+        }
+        if let Some(target) = decoded.jump_target() {
+            pending.push(target);
+        }
+        code.insert(address, decoded);
+    }
+    let reachable = code.keys().cloned().collect();
+
+    //Sweep the gaps left between reachable code
+    let mut lines = Vec::new();
+    let mut address = 0;
+    let mut data: Vec<W> = Vec::new();
+    let mut data_start = 0;
+    while address < image.len() {
+        let decoded = match code.remove(&address) {
+            Some(decoded) => Some(decoded),
+            None if !covered[address] => decode_in(image, address)
+                .filter(|decoded| !covered[address..decoded.next()].iter().any(|word| *word)),
+            None => None,
+        };
+        match decoded {
+            Some(decoded) => {
+                if !data.is_empty() {
+                    lines.push(Line::Data {
+                        address: data_start,
+                        values: data.split_off(0),
+                    });
+                }
+                address = decoded.next();
+                lines.push(Line::Code(decoded));
+            }
+            None => {
+                if data.is_empty() {
+                    data_start = address;
+                }
+                data.push(image[address].clone());
+                address += 1;
+                if data.len() == DATA_PER_LINE {
+                    lines.push(Line::Data {
+                        address: data_start,
+                        values: data.split_off(0),
+                    });
+                }
+            }
+        }
+    }
+    if !data.is_empty() {
+        lines.push(Line::Data {
+            address: data_start,
+            values: data,
+        });
+    }
+
+    //Label every jump target that starts an instruction
+    let starts: BTreeSet<usize> = lines
+        .iter()
+        .filter_map(|line| match line {
+            Line::Code(decoded) => Some(decoded.address),
+            _ => None,
+        })
+        .collect();
+    let labels = lines
+        .iter()
+        .filter_map(|line| match line {
+            Line::Code(decoded) => decoded.jump_target(),
+            _ => None,
+        })
+        .filter(|target| starts.contains(target))
+        .map(|target| (target, format!("L{}", target)))
+        .collect();
+
+    Listing {
+        lines,
+        labels,
+        reachable,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operands_show_their_mode() {
+        let decoded = decode(|address| [21101, 5, -3, 7][address], 0).unwrap();
+        assert_eq!(decoded.to_string(), "add 5, -3, [rb+7]");
+        let decoded = decode(|address| [209, -2][address], 0).unwrap();
+        assert_eq!(decoded.to_string(), "arb [rb-2]");
+        let decoded = decode(|address| [4, 10][address], 0).unwrap();
+        assert_eq!(decoded.to_string(), "out [10]");
+    }
+
+    #[test]
+    fn invalid_opcodes_are_not_code() {
+        assert!(decode(|_| 42i64, 0).is_none());
+        assert!(decode(|_| 301i64, 0).is_none());
+    }
+
+    #[test]
+    fn listing_with_labels_and_data() {
+        //Counts down from 3, printing every value
+        let image = vec![4, 12, 1001, 12, -1, 12, 1005, 12, 0, 99, 7, 8, 3];
+        let listing = disassemble(&image);
+        assert_eq!(
+            listing.to_string(),
+            "L0:\n\
+             \x20    0  out [12]\n\
+             \x20    2  add [12], -1, [12]\n\
+             \x20    6  jt [12], L0\n\
+             \x20    9  hlt\n\
+             \x20   10  data 7, 8, 3\n"
+        );
+    }
+
+    #[test]
+    fn unreached_code_is_guessed() {
+        //The jump target comes from memory, so address 5 is only found by sweeping
+        let image = vec![106, 0, 9, 99, 0, 104, 1, 99, 0, 5];
+        let listing = disassemble(&image);
+        assert!(!listing.reachable.contains(&5));
+        let text = listing.to_string();
+        assert!(text.contains("     0  jf 0, [9]\n"));
+        assert!(text.contains("     4  data 0\n"));
+        assert!(text.contains("     5  out 1  ; unreached\n"));
+        assert!(text.contains("     8  data 0, 5\n"));
+    }
+}
@@ -0,0 +1,133 @@
+use super::instruction::Instruction;
+
+//Highest address whose decoded instruction is kept
+const CACHE_LIMIT: usize = 1 << 20;
+
+//How a computer turns opcodes into instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    //Decodes the opcode again on every step. Kept as the reference the
+    //cached engine is checked against.
+    Naive,
+    //Decodes every address once and reuses it until the cell is written
+    #[default]
+    Cached,
+}
+
+//Decoded instructions by address
+#[derive(Debug, Clone, Default)]
+pub struct DecodeCache {
+    entries: Vec<Option<Instruction>>,
+}
+
+impl DecodeCache {
+    pub fn get(&self, address: usize) -> Option<Instruction> {
+        self.entries.get(address).cloned().unwrap_or(None)
+    }
+
+    pub fn insert(&mut self, address: usize, instruction: Instruction) {
+        if address >= CACHE_LIMIT {
+            return;
+        }
+        if address >= self.entries.len() {
+            self.entries.resize(address + 1, None);
+        }
+        self.entries[address] = Some(instruction);
+    }
+
+    //Forgets the instruction at an address whose cell has been written
+    pub fn invalidate(&mut self, address: usize) {
+        if let Some(entry) = self.entries.get_mut(address) {
+            *entry = None;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::assembler::assemble;
+    use crate::intcode::{Computer, State};
+
+    //Runs a program on both engines, checking they agree at every stop
+    fn check_engines_agree(image: Vec<i64>, input: Vec<i64>) -> Vec<i64> {
+        let mut naive = Computer::new(image.clone(), input.clone());
+        naive.set_engine(Engine::Naive);
+        let mut cached = Computer::new(image, input);
+        assert_eq!(cached.engine(), Engine::Cached);
+        loop {
+            let state = naive.run();
+            assert_eq!(cached.run(), state);
+            assert_eq!(cached.position(), naive.position());
+            assert_eq!(cached.steps(), naive.steps());
+            match state {
+                Ok(State::Output(_)) => (),
+                _ => break,
+            }
+        }
+        assert_eq!(cached.outputs(), naive.outputs());
+        assert_eq!(cached.snapshot().to_string(), naive.snapshot().to_string());
+        naive.outputs().to_vec()
+    }
+
+    #[test]
+    fn cache_is_invalidated_by_writes() {
+        let mut cache = DecodeCache::default();
+        let instruction = Instruction::new(1002).unwrap();
+        cache.insert(3, instruction);
+        assert_eq!(cache.get(3), Some(instruction));
+        assert_eq!(cache.get(2), None);
+        cache.invalidate(3);
+        assert_eq!(cache.get(3), None);
+        cache.invalidate(CACHE_LIMIT + 1);
+        cache.insert(CACHE_LIMIT, instruction);
+        assert_eq!(cache.get(CACHE_LIMIT), None);
+    }
+
+    #[test]
+    fn loops_agree() {
+        let source = "
+            in [n]
+            loop: mul [n], 3, [t]
+                  add [t], [acc], [acc]
+                  add [n], -1, [n]
+                  jt [n], loop
+                  out [acc]
+                  hlt
+            n: data 0
+            t: data 0
+            acc: data 0
+        ";
+        let outputs = check_engines_agree(assemble(source).unwrap(), vec![1000]);
+        assert_eq!(outputs, vec![3 * 500 * 1001]);
+    }
+
+    #[test]
+    fn self_modifying_code_agrees() {
+        //Every pass flips the instruction at op between an add and a multiply
+        let source = "
+            loop: add [op], [delta], [op]
+                  mul [delta], -1, [delta]
+            op:   add [value], 2, [value]
+                  out [value]
+                  add [n], -1, [n]
+                  jt [n], loop
+                  hlt
+            value: data 1
+            delta: data 1
+            n: data 5
+        ";
+        let outputs = check_engines_agree(assemble(source).unwrap(), vec![]);
+        assert_eq!(outputs, vec![2, 4, 8, 10, 20]);
+    }
+
+    #[test]
+    fn halting_on_input_agrees() {
+        let outputs = check_engines_agree(vec![3, 9, 4, 9, 1105, 1, 0, 99, 0, 0], vec![1, 2, 3]);
+        assert_eq!(outputs, vec![1, 2, 3]);
+    }
+}
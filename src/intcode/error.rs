@@ -0,0 +1,105 @@
+use super::instruction::DecodeError;
+use super::memory::MemoryError;
+use std::fmt;
+use std::time::Duration;
+
+//Everything that can stop an Intcode program other than halting.
+//Addresses are the position of the instruction that failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntcodeError {
+    UnknownOpcode { address: usize, opcode: i64 },
+    InvalidMode { address: usize, mode: i64 },
+    WriteInImmediateMode { address: usize },
+    NegativeAddress { address: usize, target: i64 },
+    ValueOutOfRange { address: usize },
+    RelativeBaseOverflow { address: usize },
+    Overflow { address: usize },
+    InputExhausted { address: usize },
+    InputOutOfRange { value: i64 },
+    OutputFailed { address: usize },
+    UnknownMachine { index: usize },
+    StepLimitExceeded { limit: u64 },
+    TimeLimitExceeded { limit: Duration },
+    Memory(MemoryError),
+}
+
+impl IntcodeError {
+    pub fn decode(address: usize, error: DecodeError) -> Self {
+        match error {
+            DecodeError::UnknownOpcode(opcode) => IntcodeError::UnknownOpcode { address, opcode },
+            DecodeError::InvalidMode(mode) => IntcodeError::InvalidMode { address, mode },
+        }
+    }
+
+    //Whether the program ran out of steps or time and can resume once the
+    //limit is raised
+    pub fn is_budget_exceeded(&self) -> bool {
+        matches!(
+            self,
+            IntcodeError::StepLimitExceeded { .. } | IntcodeError::TimeLimitExceeded { .. }
+        )
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntcodeError::UnknownOpcode { address, opcode } => {
+                write!(f, "Unknown opcode {} at address {}", opcode, address)
+            }
+            IntcodeError::InvalidMode { address, mode } => {
+                write!(f, "Invalid parameter mode {} at address {}", mode, address)
+            }
+            IntcodeError::WriteInImmediateMode { address } => write!(
+                f,
+                "Instruction at address {} writes to an immediate parameter",
+                address
+            ),
+            IntcodeError::NegativeAddress { address, target } => write!(
+                f,
+                "Instruction at address {} accesses negative address {}",
+                address, target
+            ),
+            IntcodeError::ValueOutOfRange { address } => write!(
+                f,
+                "Instruction at address {} uses a value too large for an address",
+                address
+            ),
+            IntcodeError::RelativeBaseOverflow { address } => {
+                write!(f, "Relative base overflows at address {}", address)
+            }
+            IntcodeError::Overflow { address } => {
+                write!(f, "Arithmetic overflow at address {}", address)
+            }
+            IntcodeError::InputExhausted { address } => {
+                write!(f, "Input exhausted at address {}", address)
+            }
+            IntcodeError::InputOutOfRange { value } => {
+                write!(f, "Input {} does not fit in a word", value)
+            }
+            IntcodeError::OutputFailed { address } => write!(
+                f,
+                "Output of the instruction at address {} could not be delivered",
+                address
+            ),
+            IntcodeError::UnknownMachine { index } => {
+                write!(f, "No machine with index {}", index)
+            }
+            IntcodeError::StepLimitExceeded { limit } => {
+                write!(f, "Step limit of {} instructions exceeded", limit)
+            }
+            IntcodeError::TimeLimitExceeded { limit } => {
+                write!(f, "Time limit of {:?} exceeded", limit)
+            }
+            IntcodeError::Memory(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for IntcodeError {}
+
+impl From<MemoryError> for IntcodeError {
+    fn from(error: MemoryError) -> Self {
+        IntcodeError::Memory(error)
+    }
+}
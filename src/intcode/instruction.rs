@@ -0,0 +1,233 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    UnknownOpcode(i64),
+    InvalidMode(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Add,
+    Multiply,
+    Save,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustRelativeBase,
+    Halt,
+}
+
+pub const OPERATIONS: [Operation; 10] = [
+    Operation::Add,
+    Operation::Multiply,
+    Operation::Save,
+    Operation::Output,
+    Operation::JumpIfTrue,
+    Operation::JumpIfFalse,
+    Operation::LessThan,
+    Operation::Equals,
+    Operation::AdjustRelativeBase,
+    Operation::Halt,
+];
+
+impl Operation {
+    pub fn from_u32(value: i64) -> Result<Operation, DecodeError> {
+        match value {
+            1 => Ok(Operation::Add),
+            2 => Ok(Operation::Multiply),
+            3 => Ok(Operation::Save),
+            4 => Ok(Operation::Output),
+            5 => Ok(Operation::JumpIfTrue),
+            6 => Ok(Operation::JumpIfFalse),
+            7 => Ok(Operation::LessThan),
+            8 => Ok(Operation::Equals),
+            9 => Ok(Operation::AdjustRelativeBase),
+            99 => Ok(Operation::Halt),
+            _ => Err(DecodeError::UnknownOpcode(value)),
+        }
+    }
+
+    //Number of parameters following the opcode
+    pub fn parameters(self) -> usize {
+        match self {
+            Operation::Add | Operation::Multiply | Operation::LessThan | Operation::Equals => 3,
+            Operation::JumpIfTrue | Operation::JumpIfFalse => 2,
+            Operation::Save | Operation::Output | Operation::AdjustRelativeBase => 1,
+            Operation::Halt => 0,
+        }
+    }
+
+    //Offset of the parameter the operation writes to, if any
+    pub fn written_parameter(self) -> Option<usize> {
+        match self {
+            Operation::Add | Operation::Multiply | Operation::LessThan | Operation::Equals => {
+                Some(3)
+            }
+            Operation::Save => Some(1),
+            _ => None,
+        }
+    }
+
+    pub fn opcode(self) -> i64 {
+        match self {
+            Operation::Add => 1,
+            Operation::Multiply => 2,
+            Operation::Save => 3,
+            Operation::Output => 4,
+            Operation::JumpIfTrue => 5,
+            Operation::JumpIfFalse => 6,
+            Operation::LessThan => 7,
+            Operation::Equals => 8,
+            Operation::AdjustRelativeBase => 9,
+            Operation::Halt => 99,
+        }
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Operation> {
+        OPERATIONS
+            .iter()
+            .cloned()
+            .find(|operation| operation.mnemonic() == mnemonic)
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Operation::Add => "add",
+            Operation::Multiply => "mul",
+            Operation::Save => "in",
+            Operation::Output => "out",
+            Operation::JumpIfTrue => "jt",
+            Operation::JumpIfFalse => "jf",
+            Operation::LessThan => "lt",
+            Operation::Equals => "eq",
+            Operation::AdjustRelativeBase => "arb",
+            Operation::Halt => "hlt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterMode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl ParameterMode {
+    pub fn from_u32(value: i64) -> Result<ParameterMode, DecodeError> {
+        match value {
+            0 => Ok(ParameterMode::Position),
+            1 => Ok(ParameterMode::Immediate),
+            2 => Ok(ParameterMode::Relative),
+            _ => Err(DecodeError::InvalidMode(value)),
+        }
+    }
+
+    //Digit used to encode the mode in an instruction
+    pub fn digit(self) -> i64 {
+        match self {
+            ParameterMode::Position => 0,
+            ParameterMode::Immediate => 1,
+            ParameterMode::Relative => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub operation: Operation,
+    pub first_mode: ParameterMode,
+    pub second_mode: ParameterMode,
+    pub third_mode: ParameterMode,
+}
+
+impl Instruction {
+    pub fn new(value: i64) -> Result<Self, DecodeError> {
+        let operation = Operation::from_u32(value % 100)?;
+        let modes = value / 100;
+        //Digits past the last parameter have to be zero
+        let extra = modes / 10i64.pow(operation.parameters() as u32);
+        if extra != 0 {
+            return Err(DecodeError::InvalidMode(extra));
+        }
+
+        Ok(Instruction {
+            operation,
+            first_mode: ParameterMode::from_u32(modes % 10)?,
+            second_mode: ParameterMode::from_u32(modes / 10 % 10)?,
+            third_mode: ParameterMode::from_u32(modes / 100 % 10)?,
+        })
+    }
+
+    //Mode of the parameter placed at the given offset from the opcode
+    pub fn mode(&self, offset: usize) -> ParameterMode {
+        match offset {
+            1 => self.first_mode,
+            2 => self.second_mode,
+            3 => self.third_mode,
+            _ => panic!("Unknown parameter offset: {}", offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_modes() {
+        let instruction = Instruction::new(1002).unwrap();
+        assert_eq!(instruction.operation, Operation::Multiply);
+        assert_eq!(instruction.first_mode, ParameterMode::Position);
+        assert_eq!(instruction.second_mode, ParameterMode::Immediate);
+        assert_eq!(instruction.third_mode, ParameterMode::Position);
+    }
+
+    #[test]
+    fn decode_relative_modes() {
+        let instruction = Instruction::new(22201).unwrap();
+        assert_eq!(instruction.operation, Operation::Add);
+        assert_eq!(instruction.mode(1), ParameterMode::Relative);
+        assert_eq!(instruction.mode(2), ParameterMode::Relative);
+        assert_eq!(instruction.mode(3), ParameterMode::Relative);
+        let instruction = Instruction::new(209).unwrap();
+        assert_eq!(instruction.operation, Operation::AdjustRelativeBase);
+        assert_eq!(instruction.mode(1), ParameterMode::Relative);
+    }
+
+    #[test]
+    fn decode_halt() {
+        let instruction = Instruction::new(99).unwrap();
+        assert_eq!(instruction.operation, Operation::Halt);
+    }
+
+    #[test]
+    fn mnemonics_and_opcodes_round_trip() {
+        for operation in OPERATIONS.iter() {
+            assert_eq!(
+                Operation::from_mnemonic(operation.mnemonic()),
+                Some(*operation)
+            );
+            assert_eq!(Operation::from_u32(operation.opcode()), Ok(*operation));
+        }
+        assert_eq!(Operation::from_mnemonic("nop"), None);
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert_eq!(Instruction::new(42), Err(DecodeError::UnknownOpcode(42)));
+        assert_eq!(Instruction::new(301), Err(DecodeError::InvalidMode(3)));
+        assert_eq!(Instruction::new(-1), Err(DecodeError::UnknownOpcode(-1)));
+    }
+
+    #[test]
+    fn modes_past_the_parameters_are_invalid() {
+        assert!(Instruction::new(11101).is_ok());
+        assert_eq!(Instruction::new(911101), Err(DecodeError::InvalidMode(9)));
+        assert_eq!(Instruction::new(111101), Err(DecodeError::InvalidMode(1)));
+        assert_eq!(Instruction::new(1104), Err(DecodeError::InvalidMode(1)));
+        assert_eq!(Instruction::new(199), Err(DecodeError::InvalidMode(1)));
+        assert!(Instruction::new(1005).is_ok());
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{Receiver, SendError, Sender};
+
+//Where a machine takes its input values from
+pub trait InputSource<W> {
+    //Next value for the program, None when no input is available
+    fn next_input(&mut self) -> Option<W>;
+}
+
+//Where a machine sends its output values to
+pub trait OutputSink<W> {
+    fn send(&mut self, value: W);
+
+    //Whether the sink stopped taking values, which stops the machine
+    fn failed(&self) -> bool {
+        false
+    }
+}
+
+impl<W> InputSource<W> for VecDeque<W> {
+    fn next_input(&mut self) -> Option<W> {
+        self.pop_front()
+    }
+}
+
+impl<W> OutputSink<W> for VecDeque<W> {
+    fn send(&mut self, value: W) {
+        self.push_back(value);
+    }
+}
+
+impl<W> OutputSink<W> for Vec<W> {
+    fn send(&mut self, value: W) {
+        self.push(value);
+    }
+}
+
+//Takes input from any iterator
+pub struct IterSource<I>(pub I);
+
+impl<W, I: Iterator<Item = W>> InputSource<W> for IterSource<I> {
+    fn next_input(&mut self) -> Option<W> {
+        self.0.next()
+    }
+}
+
+//Asks a closure for every input value
+pub struct FnSource<F>(pub F);
+
+impl<W, F: FnMut() -> Option<W>> InputSource<W> for FnSource<F> {
+    fn next_input(&mut self) -> Option<W> {
+        (self.0)()
+    }
+}
+
+//Hands every output value to a closure
+pub struct FnSink<F>(pub F);
+
+impl<W, F: FnMut(W)> OutputSink<W> for FnSink<F> {
+    fn send(&mut self, value: W) {
+        (self.0)(value)
+    }
+}
+
+//Blocks on a channel for every input value. A closed channel means no input.
+pub struct ChannelSource<W>(pub Receiver<W>);
+
+impl<W> InputSource<W> for ChannelSource<W> {
+    fn next_input(&mut self) -> Option<W> {
+        self.0.recv().ok()
+    }
+}
+
+//Sends every output value through a channel. Once nobody listens the values
+//are dropped, the first one being kept in the error.
+pub struct ChannelSink<W> {
+    sender: Sender<W>,
+    error: Option<SendError<W>>,
+}
+
+impl<W> ChannelSink<W> {
+    pub fn new(sender: Sender<W>) -> Self {
+        ChannelSink {
+            sender,
+            error: None,
+        }
+    }
+
+    //Error that stopped the sending, if any
+    pub fn error(&self) -> Option<&SendError<W>> {
+        self.error.as_ref()
+    }
+}
+
+impl<W> OutputSink<W> for ChannelSink<W> {
+    fn send(&mut self, value: W) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(error) = self.sender.send(value) {
+            self.error = Some(error);
+        }
+    }
+
+    fn failed(&self) -> bool {
+        self.error.is_some()
+    }
+}
+
+//Reads input values from text, one or more per line separated by commas or
+//whitespace. Reading stops at the end of the text or at the first error.
+pub struct LineSource<R> {
+    reader: R,
+    pending: VecDeque<String>,
+    error: Option<io::Error>,
+}
+
+impl<R: BufRead> LineSource<R> {
+    pub fn new(reader: R) -> Self {
+        LineSource {
+            reader,
+            pending: VecDeque::new(),
+            error: None,
+        }
+    }
+
+    //Error that stopped the reading, if any
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    fn fill(&mut self) -> io::Result<bool> {
+        while self.pending.is_empty() {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(false);
+            }
+            self.pending.extend(
+                line.split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|token| !token.is_empty())
+                    .map(String::from),
+            );
+        }
+        Ok(true)
+    }
+}
+
+impl<W: std::str::FromStr, R: BufRead> InputSource<W> for LineSource<R> {
+    fn next_input(&mut self) -> Option<W> {
+        if self.error.is_some() {
+            return None;
+        }
+        match self.fill() {
+            Ok(true) => (),
+            Ok(false) => return None,
+            Err(error) => {
+                self.error = Some(error);
+                return None;
+            }
+        }
+        let token = self.pending.pop_front()?;
+        match token.parse() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                self.error = Some(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid input value: {}", token),
+                ));
+                None
+            }
+        }
+    }
+}
+
+//Writes every output value on its own line. Writing stops at the first
+//error.
+pub struct WriterSink<T: Write> {
+    writer: T,
+    error: Option<io::Error>,
+}
+
+impl<T: Write> WriterSink<T> {
+    pub fn new(writer: T) -> Self {
+        WriterSink {
+            writer,
+            error: None,
+        }
+    }
+
+    //Error that stopped the writing, if any
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.writer
+    }
+}
+
+impl<W: std::fmt::Display, T: Write> OutputSink<W> for WriterSink<T> {
+    fn send(&mut self, value: W) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(error) = writeln!(self.writer, "{}", value) {
+            self.error = Some(error);
+        }
+    }
+
+    fn failed(&self) -> bool {
+        self.error.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn line_source_splits_values() {
+        let mut source = LineSource::new(Cursor::new("1, 2\n\n3 -4\n"));
+        let values: Vec<i64> = std::iter::from_fn(|| source.next_input()).collect();
+        assert_eq!(values, vec![1, 2, 3, -4]);
+        assert!(source.error().is_none());
+    }
+
+    #[test]
+    fn line_source_stops_at_invalid_values() {
+        let mut source = LineSource::new(Cursor::new("1,x,3"));
+        assert_eq!(source.next_input(), Some(1i64));
+        assert_eq!(source.next_input(), None::<i64>);
+        assert_eq!(
+            source.error().map(|error| error.kind()),
+            Some(io::ErrorKind::InvalidData)
+        );
+    }
+
+    #[test]
+    fn writer_sink_writes_lines() {
+        let mut sink = WriterSink::new(Vec::new());
+        sink.send(1i64);
+        sink.send(-2i64);
+        assert!(sink.error().is_none());
+        assert_eq!(sink.into_inner(), b"1\n-2\n");
+    }
+
+    #[test]
+    fn sinks_keep_their_first_error() {
+        let mut buffer = [0u8; 3];
+        let mut sink = WriterSink::new(&mut buffer[..]);
+        sink.send(12i64);
+        sink.send(34i64);
+        assert_eq!(
+            sink.error().map(|error| error.kind()),
+            Some(io::ErrorKind::WriteZero)
+        );
+        assert_eq!(&buffer, b"12\n");
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut sink = ChannelSink::new(sender);
+        sink.send(1i64);
+        drop(receiver);
+        sink.send(2i64);
+        sink.send(3i64);
+        assert_eq!(sink.error().map(|error| error.0), Some(2));
+    }
+}
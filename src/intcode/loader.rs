@@ -0,0 +1,134 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+//Reads programs in the puzzle format: values separated by commas, with any
+//whitespace or line breaks around them. The build script shares this file
+//through a path attribute, so it only relies on std.
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    //Text found where a value was expected, empty when there was none.
+    //Lines and columns count from 1.
+    InvalidValue {
+        line: usize,
+        column: usize,
+        token: String,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(error) => write!(f, "Cannot read program: {}", error),
+            LoadError::InvalidValue {
+                line,
+                column,
+                token,
+            } if token.is_empty() => {
+                write!(f, "Missing value at line {}, column {}", line, column)
+            }
+            LoadError::InvalidValue {
+                line,
+                column,
+                token,
+            } => write!(
+                f,
+                "Invalid value {:?} at line {}, column {}",
+                token, line, column
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(error: io::Error) -> Self {
+        LoadError::Io(error)
+    }
+}
+
+pub fn parse_program<W: FromStr>(text: &str) -> Result<Vec<W>, LoadError> {
+    let mut program = Vec::new();
+    let mut offset = 0;
+    for token in text.split(',') {
+        let start = offset + token.len() - token.trim_start().len();
+        offset += token.len() + 1;
+        let token = token.trim();
+        match token.parse() {
+            Ok(value) => program.push(value),
+            Err(_) => {
+                let (line, column) = position(text, start);
+                return Err(LoadError::InvalidValue {
+                    line,
+                    column,
+                    token: token.to_string(),
+                });
+            }
+        }
+    }
+    Ok(program)
+}
+
+pub fn load_program<W: FromStr, P: AsRef<Path>>(path: P) -> Result<Vec<W>, LoadError> {
+    parse_program(&fs::read_to_string(path)?)
+}
+
+//Line and column of a byte offset in the text
+fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> String {
+        parse_program::<i64>(text).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn whitespace_is_ignored() {
+        let program: Vec<i64> = parse_program(" 1,0, 0,3,\n  99 ,-7\n").unwrap();
+        assert_eq!(program, vec![1, 0, 0, 3, 99, -7]);
+        let program: Vec<i64> = load_program("input/day5.txt").unwrap();
+        assert_eq!(program.len(), 678);
+    }
+
+    #[test]
+    fn errors_point_at_the_value() {
+        assert_eq!(
+            error("1,2,\n3, x4 ,5"),
+            "Invalid value \"x4\" at line 2, column 4"
+        );
+        assert_eq!(
+            error("1,2\n3,4"),
+            "Invalid value \"2\\n3\" at line 1, column 3"
+        );
+        assert_eq!(error("1,,2"), "Missing value at line 1, column 3");
+        assert_eq!(error(""), "Missing value at line 1, column 1");
+        assert_eq!(
+            error("1,99999999999999999999"),
+            "Invalid value \"99999999999999999999\" at line 1, column 3"
+        );
+        assert_eq!(
+            parse_program::<i32>("1,9223372036854775807")
+                .unwrap_err()
+                .to_string(),
+            "Invalid value \"9223372036854775807\" at line 1, column 3"
+        );
+        match load_program::<i64, _>("input/missing.txt") {
+            Err(LoadError::Io(error)) => assert_eq!(error.kind(), io::ErrorKind::NotFound),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use super::word::Word;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+//Addresses this close to the end of the dense vector grow it instead of
+//going to the sparse map
+const DENSE_GROWTH: usize = 4096;
+
+//Default hard cap on the number of cells a machine may allocate
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 24;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryError {
+    LimitExceeded { address: usize, limit: usize },
+}
+
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MemoryError::LimitExceeded { address, limit } => write!(
+                f,
+                "Writing address {} exceeds the memory limit of {} cells",
+                address, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MemoryError {}
+
+//Unbounded, zero initialised memory. Cells near the program image live in a
+//dense vector and far away ones in a sparse map. Clones share both until one
+//of them writes.
+#[derive(Debug, Clone)]
+pub struct Memory<W: Word> {
+    dense: Arc<Vec<W>>,
+    sparse: Arc<HashMap<usize, W>>,
+    limit: usize,
+}
+
+impl<W: Word> Memory<W> {
+    pub fn new(image: Vec<W>) -> Self {
+        Memory {
+            dense: Arc::new(image),
+            sparse: Arc::new(HashMap::new()),
+            limit: DEFAULT_MEMORY_LIMIT,
+        }
+    }
+
+    //Rebuilds memory from the cells returned by dense and sparse
+    pub fn from_parts(
+        dense: Vec<W>,
+        sparse: Vec<(usize, W)>,
+        limit: usize,
+    ) -> Result<Self, MemoryError> {
+        let mut memory = Memory::new(dense);
+        memory.limit = limit;
+        memory.check_limit(memory.dense.len().saturating_sub(1), memory.allocated())?;
+        for (address, value) in sparse {
+            memory.write(address, value)?;
+        }
+        Ok(memory)
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    //Contiguous cells starting at address 0
+    pub fn dense(&self) -> &[W] {
+        &self.dense
+    }
+
+    //Cells allocated past the dense part, by address
+    pub fn sparse(&self) -> Vec<(usize, &W)> {
+        let mut cells: Vec<_> = self
+            .sparse
+            .iter()
+            .map(|(address, value)| (*address, value))
+            .collect();
+        cells.sort_by_key(|(address, _)| *address);
+        cells
+    }
+
+    //Number of cells currently allocated
+    pub fn allocated(&self) -> usize {
+        self.dense.len() + self.sparse.len()
+    }
+
+    pub fn read(&self, address: usize) -> W {
+        match self.dense.get(address) {
+            Some(value) => value.clone(),
+            None => self.sparse.get(&address).cloned().unwrap_or_else(W::zero),
+        }
+    }
+
+    pub fn write(&mut self, address: usize, value: W) -> Result<(), MemoryError> {
+        if address < self.dense.len() {
+            Arc::make_mut(&mut self.dense)[address] = value;
+            return Ok(());
+        }
+        if self.sparse.contains_key(&address) {
+            Arc::make_mut(&mut self.sparse).insert(address, value);
+            return Ok(());
+        }
+        if address - self.dense.len() < DENSE_GROWTH {
+            self.grow(address + 1)?;
+            Arc::make_mut(&mut self.dense)[address] = value;
+        } else {
+            self.check_limit(address, self.allocated() + 1)?;
+            Arc::make_mut(&mut self.sparse).insert(address, value);
+        }
+        Ok(())
+    }
+
+    //Extends the dense vector, pulling in any sparse cell it now covers
+    fn grow(&mut self, len: usize) -> Result<(), MemoryError> {
+        let start = self.dense.len();
+        let moved = self.sparse.keys().filter(|address| **address < len).count();
+        self.check_limit(len - 1, self.allocated() + len - start - moved)?;
+        let dense = Arc::make_mut(&mut self.dense);
+        dense.resize(len, W::zero());
+        if moved > 0 {
+            let sparse = Arc::make_mut(&mut self.sparse);
+            for (address, cell) in dense.iter_mut().enumerate().skip(start) {
+                if let Some(value) = sparse.remove(&address) {
+                    *cell = value;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_limit(&self, address: usize, cells: usize) -> Result<(), MemoryError> {
+        if cells > self.limit {
+            Err(MemoryError::LimitExceeded {
+                address,
+                limit: self.limit,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unwritten_cells_are_zero() {
+        let memory = Memory::new(vec![1i64, 2, 3]);
+        assert_eq!(memory.read(1), 2);
+        assert_eq!(memory.read(3), 0);
+        assert_eq!(memory.read(1 << 40), 0);
+    }
+
+    #[test]
+    fn nearby_writes_grow_dense_memory() {
+        let mut memory = Memory::new(vec![1i64, 2, 3]);
+        memory.write(10, 7).unwrap();
+        assert_eq!(memory.read(10), 7);
+        assert_eq!(memory.dense.len(), 11);
+        assert!(memory.sparse.is_empty());
+    }
+
+    #[test]
+    fn far_writes_go_to_sparse_memory() {
+        let mut memory = Memory::new(vec![1i64, 2, 3]);
+        memory.write(1_000_000, 5).unwrap();
+        assert_eq!(memory.read(1_000_000), 5);
+        assert_eq!(memory.allocated(), 4);
+        //Growing the dense part absorbs the sparse cells it covers
+        memory.write(1_000_000 - 10, 6).unwrap();
+        memory.grow(1_000_001).unwrap();
+        assert_eq!(memory.read(1_000_000), 5);
+        assert!(memory.sparse.is_empty());
+    }
+
+    #[test]
+    fn limit_is_enforced() {
+        let mut memory = Memory::new(vec![0i64; 8]);
+        memory.set_limit(10);
+        assert!(memory.write(9, 1).is_ok());
+        assert_eq!(
+            memory.write(10, 1),
+            Err(MemoryError::LimitExceeded {
+                address: 10,
+                limit: 10
+            })
+        );
+        assert!(memory.write(1 << 30, 1).is_err());
+    }
+
+    #[test]
+    fn clones_copy_on_write() {
+        let mut memory = Memory::new(vec![1i64, 2, 3]);
+        memory.write(1_000_000, 4).unwrap();
+        let mut clone = memory.clone();
+        assert!(Arc::ptr_eq(&memory.dense, &clone.dense));
+        clone.write(0, 9).unwrap();
+        assert!(!Arc::ptr_eq(&memory.dense, &clone.dense));
+        assert!(Arc::ptr_eq(&memory.sparse, &clone.sparse));
+        assert_eq!(memory.read(0), 1);
+        assert_eq!(clone.read(0), 9);
+        clone.write(1_000_000, 5).unwrap();
+        assert_eq!(memory.read(1_000_000), 4);
+    }
+
+    #[test]
+    fn rebuilt_from_parts() {
+        let mut memory = Memory::new(vec![1i64, 2, 3]);
+        memory.write(1_000_000, 4).unwrap();
+        let sparse = memory
+            .sparse()
+            .into_iter()
+            .map(|(address, value)| (address, *value))
+            .collect();
+        let rebuilt = Memory::from_parts(memory.dense().to_vec(), sparse, 100).unwrap();
+        assert_eq!(rebuilt.dense(), &[1, 2, 3]);
+        assert_eq!(rebuilt.read(1_000_000), 4);
+        assert!(Memory::from_parts(vec![0i64; 8], vec![], 4).is_err());
+    }
+}
@@ -0,0 +1,501 @@
+use super::computer::{Computer, State};
+use super::error::IntcodeError;
+use super::scheduler::{yield_now, Scheduler};
+use super::word::Word;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+//Machines running as cooperative tasks wired together with channels. A
+//machine waits on its channel whenever it needs input and lets the others
+//run after every output.
+
+struct Queue<W> {
+    values: VecDeque<W>,
+    senders: usize,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl<W> Queue<W> {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+pub struct Sender<W> {
+    queue: Arc<Mutex<Queue<W>>>,
+}
+
+pub struct Receiver<W> {
+    queue: Arc<Mutex<Queue<W>>>,
+}
+
+//Unbounded channel with any number of senders and a single receiver, which
+//can be waited on by one task at a time
+pub fn channel<W>() -> (Sender<W>, Receiver<W>) {
+    let queue = Arc::new(Mutex::new(Queue {
+        values: VecDeque::new(),
+        senders: 1,
+        closed: false,
+        waker: None,
+    }));
+    (
+        Sender {
+            queue: queue.clone(),
+        },
+        Receiver { queue },
+    )
+}
+
+impl<W> Sender<W> {
+    pub fn send(&self, value: W) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.values.push_back(value);
+        queue.wake();
+    }
+
+    //Stops the receiver from waiting once the values sent so far are taken,
+    //even if senders are left
+    pub fn close(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.closed = true;
+        queue.wake();
+    }
+}
+
+impl<W> Clone for Sender<W> {
+    fn clone(&self) -> Self {
+        self.queue.lock().unwrap().senders += 1;
+        Sender {
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<W> Drop for Sender<W> {
+    fn drop(&mut self) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.senders -= 1;
+        if queue.senders == 0 {
+            queue.wake();
+        }
+    }
+}
+
+impl<W> Receiver<W> {
+    //Waits for the next value, None once the channel is empty and either
+    //closed or without senders
+    pub fn recv(&mut self) -> Recv<'_, W> {
+        Recv(self)
+    }
+
+    pub fn try_recv(&mut self) -> Option<W> {
+        self.queue.lock().unwrap().values.pop_front()
+    }
+}
+
+pub struct Recv<'a, W>(&'a mut Receiver<W>);
+
+impl<'a, W> Future for Recv<'a, W> {
+    type Output = Option<W>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<W>> {
+        let mut queue = self.0.queue.lock().unwrap();
+        if let Some(value) = queue.values.pop_front() {
+            Poll::Ready(Some(value))
+        } else if queue.closed || queue.senders == 0 {
+            Poll::Ready(None)
+        } else {
+            queue.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+//Runs a machine as a task taking its input from a channel and sending every
+//output to all the given channels. Ends when the machine halts or needs input
+//that will never come, giving the machine back.
+pub async fn machine<W: Word>(
+    mut computer: Computer<W>,
+    mut input: Receiver<W>,
+    outputs: Vec<Sender<W>>,
+) -> Result<Computer<W>, IntcodeError> {
+    loop {
+        match computer.run()? {
+            State::Halted => return Ok(computer),
+            State::AwaitingInput => match input.recv().await {
+                Some(value) => computer.add_input(value),
+                None => return Ok(computer),
+            },
+            State::Output(value) => {
+                for output in &outputs {
+                    output.send(value.clone());
+                }
+                yield_now().await;
+            }
+        }
+    }
+}
+
+//How a machine fed by several others combines their values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Merge {
+    //Every value as soon as it is sent
+    #[default]
+    Arrival,
+    //One value from each machine in the order they were connected
+    Ordered,
+    //The largest of one value from each machine
+    Max,
+    //The smallest of one value from each machine
+    Min,
+}
+
+//Waits for one value on every input and passes them on combined, until one
+//of the inputs runs dry
+async fn merge<W: Word>(
+    mut inputs: Vec<Receiver<W>>,
+    output: Sender<W>,
+    policy: Merge,
+) -> Result<Option<Computer<W>>, IntcodeError> {
+    loop {
+        let mut values = Vec::with_capacity(inputs.len());
+        for input in inputs.iter_mut() {
+            match input.recv().await {
+                Some(value) => values.push(value),
+                None => return Ok(None),
+            }
+        }
+        let best = |keep: fn(&W, &W) -> bool| {
+            values
+                .iter()
+                .cloned()
+                .fold(None, |best: Option<W>, value| match best {
+                    Some(best) if !keep(&value, &best) => Some(best),
+                    _ => Some(value),
+                })
+        };
+        match policy {
+            Merge::Max => {
+                output.send(best(|value, best| value > best).expect("Merges have inputs"))
+            }
+            Merge::Min => {
+                output.send(best(|value, best| value < best).expect("Merges have inputs"))
+            }
+            Merge::Arrival | Merge::Ordered => {
+                values.into_iter().for_each(|value| output.send(value))
+            }
+        }
+        yield_now().await;
+    }
+}
+
+//Machines whose outputs feed the inputs of others. A machine may feed and be
+//fed by any number of machines, itself included. Every output also stays
+//buffered in the machine that produced it.
+pub struct Network<W: Word> {
+    machines: Vec<Computer<W>>,
+    links: Vec<(usize, usize)>,
+    sent: Vec<Vec<W>>,
+    merges: Vec<Merge>,
+}
+
+impl<W: Word> Default for Network<W> {
+    fn default() -> Self {
+        Network::new()
+    }
+}
+
+impl<W: Word> Network<W> {
+    pub fn new() -> Self {
+        Network {
+            machines: Vec::new(),
+            links: Vec::new(),
+            sent: Vec::new(),
+            merges: Vec::new(),
+        }
+    }
+
+    //Adds a machine, returning the index used to connect it
+    pub fn add_machine(&mut self, computer: Computer<W>) -> usize {
+        self.machines.push(computer);
+        self.sent.push(Vec::new());
+        self.merges.push(Merge::default());
+        self.machines.len() - 1
+    }
+
+    fn check(&self, index: usize) -> Result<(), IntcodeError> {
+        if index < self.machines.len() {
+            Ok(())
+        } else {
+            Err(IntcodeError::UnknownMachine { index })
+        }
+    }
+
+    //Sends every output of one machine to another
+    pub fn connect(&mut self, from: usize, to: usize) -> Result<(), IntcodeError> {
+        self.check(from)?;
+        self.check(to)?;
+        self.links.push((from, to));
+        Ok(())
+    }
+
+    //How a machine combines the values of the machines feeding it. Values
+    //queued with send skip the merge.
+    pub fn set_merge(&mut self, machine: usize, merge: Merge) -> Result<(), IntcodeError> {
+        self.check(machine)?;
+        self.merges[machine] = merge;
+        Ok(())
+    }
+
+    //Queues a value on the input channel of a machine
+    pub fn send(&mut self, to: usize, value: W) -> Result<(), IntcodeError> {
+        self.check(to)?;
+        self.sent[to].push(value);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.machines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.machines.is_empty()
+    }
+
+    //Runs every machine on the current thread until none can make progress
+    //and gives them back in the order they were added. Machines left waiting
+    //for input are not halted.
+    pub fn run(self) -> Result<Vec<Computer<W>>, IntcodeError> {
+        self.execute(|scheduler| scheduler.run())
+    }
+
+    //Same as run, with the machines spread over several threads
+    pub fn run_threaded(self, threads: usize) -> Result<Vec<Computer<W>>, IntcodeError> {
+        self.execute(|scheduler| scheduler.run_threaded(threads))
+    }
+
+    fn execute<F>(self, run: F) -> Result<Vec<Computer<W>>, IntcodeError>
+    where
+        F: Fn(&mut Scheduler<Result<Option<Computer<W>>, IntcodeError>>),
+    {
+        let Network {
+            machines,
+            links,
+            sent,
+            merges,
+        } = self;
+        let (senders, receivers): (Vec<_>, Vec<_>) = machines.iter().map(|_| channel()).unzip();
+        for (sender, values) in senders.iter().zip(sent) {
+            values.into_iter().for_each(|value| sender.send(value));
+        }
+        //Every channel, to close them all once the network is stuck
+        let mut channels: Vec<Sender<W>> = senders.to_vec();
+        //Where each link delivers its values, the merge of the machine it
+        //feeds if it has one
+        let mut targets: Vec<Sender<W>> =
+            links.iter().map(|(_, to)| senders[*to].clone()).collect();
+        let mut mergers = Vec::new();
+        for (index, policy) in merges.iter().enumerate() {
+            let incoming: Vec<_> = (0..links.len())
+                .filter(|link| links[*link].1 == index)
+                .collect();
+            if *policy == Merge::Arrival || incoming.is_empty() {
+                continue;
+            }
+            let mut inputs = Vec::new();
+            for link in incoming {
+                let (sender, receiver) = channel();
+                channels.push(sender.clone());
+                targets[link] = sender;
+                inputs.push(receiver);
+            }
+            mergers.push(merge(inputs, senders[index].clone(), *policy));
+        }
+        let mut scheduler = Scheduler::new();
+        for (index, (computer, input)) in machines.into_iter().zip(receivers).enumerate() {
+            let outputs = (0..links.len())
+                .filter(|link| links[*link].0 == index)
+                .map(|link| targets[link].clone())
+                .collect();
+            scheduler.spawn(async move { machine(computer, input, outputs).await.map(Some) });
+        }
+        mergers.into_iter().for_each(|merger| {
+            scheduler.spawn(merger);
+        });
+        drop(targets);
+        run(&mut scheduler);
+        //The machines still running wait for each other, wake them up empty
+        //handed so they give their state back
+        if !scheduler.all_done() {
+            channels.iter().for_each(|sender| sender.close());
+            run(&mut scheduler);
+        }
+        let mut machines = Vec::new();
+        for id in 0..scheduler.len() {
+            if let Some(computer) = scheduler.take(id).expect("Every task has stopped")? {
+                machines.push(computer);
+            }
+        }
+        Ok(machines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::assembler::assembled;
+    use crate::intcode::loader::load_program;
+    use itertools::Itertools;
+
+    //Signal out of the day7 amplifiers connected in a ring
+    fn feedback(phases: &[i64], threads: Option<usize>) -> i64 {
+        let image: Vec<i64> = load_program("input/day7.txt").unwrap();
+        let mut network = Network::new();
+        for phase in phases {
+            network.add_machine(Computer::new(image.clone(), vec![*phase]));
+        }
+        for index in 0..phases.len() {
+            network.connect(index, (index + 1) % phases.len()).unwrap();
+        }
+        network.send(0, 0).unwrap();
+        let amplifiers = match threads {
+            Some(threads) => network.run_threaded(threads),
+            None => network.run(),
+        }
+        .unwrap();
+        assert!(amplifiers.iter().all(|amplifier| amplifier.is_halted()));
+        *amplifiers.last().unwrap().last_output().unwrap()
+    }
+
+    #[test]
+    fn amplifier_ring() {
+        let best = (5..10)
+            .permutations(5)
+            .map(|phases| {
+                let signal = feedback(&phases, None);
+                assert_eq!(feedback(&phases, Some(3)), signal);
+                signal
+            })
+            .max();
+        assert_eq!(best, Some(5406484));
+    }
+
+    //Echoes its input after adding a constant
+    fn adder(constant: i64) -> Computer {
+        assembled(&format!(
+            "loop: in [value]
+                   add [value], {}, [value]
+                   out [value]
+                   jt 1, loop
+             value: data 0",
+            constant
+        ))
+    }
+
+    #[test]
+    fn fan_out_and_fan_in() {
+        let mut network = Network::new();
+        let source = network.add_machine(assembled("out 1\nout 2\nhlt"));
+        let tens = network.add_machine(adder(10));
+        let hundreds = network.add_machine(adder(100));
+        let sink = network.add_machine(adder(0));
+        network.connect(source, tens).unwrap();
+        network.connect(source, hundreds).unwrap();
+        network.connect(tens, sink).unwrap();
+        network.connect(hundreds, sink).unwrap();
+        let machines = network.run().unwrap();
+        assert!(machines[source].is_halted());
+        //The adders never halt and are left waiting for input
+        assert!(!machines[sink].is_halted());
+        assert_eq!(machines[sink].outputs(), &[11, 101, 12, 102]);
+    }
+
+    #[test]
+    fn merges_wait_for_every_machine() {
+        for (merge, outputs) in [
+            (Merge::Ordered, vec![11, 101, 12, 102]),
+            (Merge::Max, vec![101, 102]),
+            (Merge::Min, vec![11, 12]),
+        ]
+        .iter()
+        {
+            let mut network = Network::new();
+            let source = network.add_machine(assembled("out 1\nout 2\nhlt"));
+            let hundreds = network.add_machine(adder(100));
+            let tens = network.add_machine(adder(10));
+            let sink = network.add_machine(adder(0));
+            network.connect(source, hundreds).unwrap();
+            network.connect(source, tens).unwrap();
+            network.connect(tens, sink).unwrap();
+            network.connect(hundreds, sink).unwrap();
+            network.set_merge(sink, *merge).unwrap();
+            let machines = network.run().unwrap();
+            assert_eq!(machines.len(), 4);
+            assert_eq!(machines[sink].outputs(), &outputs[..]);
+        }
+    }
+
+    #[test]
+    fn deadlocks_stop_the_network() {
+        let mut network = Network::new();
+        let a = network.add_machine(adder(1));
+        let b = network.add_machine(adder(1));
+        network.connect(a, b).unwrap();
+        network.connect(b, a).unwrap();
+        let machines = network.run_threaded(2).unwrap();
+        assert!(machines.iter().all(|machine| !machine.is_halted()));
+        assert!(machines[a].outputs().is_empty());
+        assert!(machines[b].outputs().is_empty());
+    }
+
+    #[test]
+    fn errors_stop_the_network() {
+        let mut network = Network::new();
+        network.add_machine(assembled("in [0]\nhlt"));
+        let faulty = network.add_machine(Computer::new(vec![42], vec![]));
+        network.connect(faulty, 0).unwrap();
+        assert_eq!(
+            network.connect(faulty, 2),
+            Err(IntcodeError::UnknownMachine { index: 2 })
+        );
+        assert_eq!(
+            network.send(5, 1),
+            Err(IntcodeError::UnknownMachine { index: 5 })
+        );
+        match network.run() {
+            Err(error) => assert_eq!(
+                error,
+                IntcodeError::UnknownOpcode {
+                    address: 0,
+                    opcode: 42
+                }
+            ),
+            Ok(_) => panic!("The faulty machine should stop the network"),
+        }
+    }
+
+    #[test]
+    fn closed_channels_end_the_wait() {
+        let (sender, mut receiver) = channel();
+        sender.send(1);
+        let other = sender.clone();
+        drop(sender);
+        let mut scheduler = Scheduler::new();
+        let task = scheduler.spawn(async move {
+            let first = receiver.recv().await;
+            let second = receiver.recv().await;
+            (first, second)
+        });
+        scheduler.run();
+        assert!(!scheduler.is_done(task));
+        drop(other);
+        scheduler.run();
+        assert_eq!(scheduler.take(task), Some((Some(1), None)));
+    }
+}
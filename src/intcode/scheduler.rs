@@ -0,0 +1,249 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread;
+
+//Executor for cooperative tasks. Tasks are polled in the order they were
+//woken, so running on a single thread always interleaves them the same way.
+//Running stops once no task can make progress, either because they are all
+//done or because the rest wait on something nobody will send.
+
+type Task<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+enum Slot<T> {
+    Pending(Task<T>),
+    Done(T),
+    Taken,
+}
+
+//Tasks waiting to be polled and how many are being polled right now
+#[derive(Default)]
+struct ReadyQueue {
+    ids: VecDeque<usize>,
+    queued: Vec<bool>,
+    busy: usize,
+}
+
+#[derive(Default)]
+struct Ready {
+    queue: Mutex<ReadyQueue>,
+    signal: Condvar,
+}
+
+impl Ready {
+    fn push(&self, id: usize) {
+        let mut queue = self.queue.lock().unwrap();
+        if !queue.queued[id] {
+            queue.queued[id] = true;
+            queue.ids.push_back(id);
+            self.signal.notify_one();
+        }
+    }
+
+    fn pop(queue: &mut ReadyQueue) -> Option<usize> {
+        let id = queue.ids.pop_front()?;
+        queue.queued[id] = false;
+        Some(id)
+    }
+}
+
+struct TaskWaker {
+    id: usize,
+    ready: Arc<Ready>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.ready.push(self.id);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.ready.push(self.id);
+    }
+}
+
+pub struct Scheduler<T> {
+    slots: Vec<Mutex<Slot<T>>>,
+    ready: Arc<Ready>,
+}
+
+impl<T: Send> Default for Scheduler<T> {
+    fn default() -> Self {
+        Scheduler::new()
+    }
+}
+
+impl<T: Send> Scheduler<T> {
+    pub fn new() -> Self {
+        Scheduler {
+            slots: Vec::new(),
+            ready: Arc::new(Ready::default()),
+        }
+    }
+
+    //Adds a task, which first runs on the next call to run. Returns its id.
+    pub fn spawn<F>(&mut self, task: F) -> usize
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        let id = self.slots.len();
+        self.slots.push(Mutex::new(Slot::Pending(Box::pin(task))));
+        self.ready.queue.lock().unwrap().queued.push(false);
+        self.ready.push(id);
+        id
+    }
+
+    //Runs every task on the current thread until none can make progress
+    pub fn run(&mut self) {
+        loop {
+            let id = Ready::pop(&mut self.ready.queue.lock().unwrap());
+            match id {
+                Some(id) => self.poll(id),
+                None => return,
+            }
+        }
+    }
+
+    //Same as run, spreading the tasks over several threads. The order tasks
+    //run in is then up to the threads.
+    pub fn run_threaded(&mut self, threads: usize) {
+        let scheduler = &*self;
+        thread::scope(|scope| {
+            for _ in 0..threads.max(1) {
+                scope.spawn(move || scheduler.work());
+            }
+        });
+    }
+
+    fn work(&self) {
+        loop {
+            let id = {
+                let mut queue = self.ready.queue.lock().unwrap();
+                loop {
+                    if let Some(id) = Ready::pop(&mut queue) {
+                        queue.busy += 1;
+                        break id;
+                    }
+                    if queue.busy == 0 {
+                        self.ready.signal.notify_all();
+                        return;
+                    }
+                    queue = self.ready.signal.wait(queue).unwrap();
+                }
+            };
+            self.poll(id);
+            let mut queue = self.ready.queue.lock().unwrap();
+            queue.busy -= 1;
+            if queue.busy == 0 && queue.ids.is_empty() {
+                self.ready.signal.notify_all();
+            }
+        }
+    }
+
+    fn poll(&self, id: usize) {
+        let mut slot = self.slots[id].lock().unwrap();
+        if let Slot::Pending(task) = &mut *slot {
+            let waker = Waker::from(Arc::new(TaskWaker {
+                id,
+                ready: self.ready.clone(),
+            }));
+            if let Poll::Ready(value) = task.as_mut().poll(&mut Context::from_waker(&waker)) {
+                *slot = Slot::Done(value);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn is_done(&self, id: usize) -> bool {
+        !matches!(*self.slots[id].lock().unwrap(), Slot::Pending(_))
+    }
+
+    //Whether every task has finished
+    pub fn all_done(&self) -> bool {
+        (0..self.slots.len()).all(|id| self.is_done(id))
+    }
+
+    //Result of a finished task, given out only once
+    pub fn take(&mut self, id: usize) -> Option<T> {
+        let slot = self.slots[id].get_mut().unwrap();
+        match std::mem::replace(slot, Slot::Taken) {
+            Slot::Done(value) => Some(value),
+            other => {
+                *slot = other;
+                None
+            }
+        }
+    }
+}
+
+//Lets the other ready tasks run before carrying on
+pub fn yield_now() -> YieldNow {
+    YieldNow(false)
+}
+
+pub struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.0 {
+            Poll::Ready(())
+        } else {
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Records its name a few times, yielding in between
+    async fn count(name: char, times: usize, log: Arc<Mutex<String>>) -> usize {
+        for _ in 0..times {
+            log.lock().unwrap().push(name);
+            yield_now().await;
+        }
+        times
+    }
+
+    #[test]
+    fn tasks_take_turns() {
+        let log = Arc::new(Mutex::new(String::new()));
+        let mut scheduler = Scheduler::new();
+        let a = scheduler.spawn(count('a', 3, log.clone()));
+        let b = scheduler.spawn(count('b', 2, log.clone()));
+        assert!(!scheduler.is_done(a));
+        scheduler.run();
+        assert_eq!(*log.lock().unwrap(), "ababa");
+        assert!(scheduler.all_done());
+        assert_eq!(scheduler.take(a), Some(3));
+        assert_eq!(scheduler.take(a), None);
+        assert_eq!(scheduler.take(b), Some(2));
+    }
+
+    #[test]
+    fn threads_run_every_task() {
+        let log = Arc::new(Mutex::new(String::new()));
+        let mut scheduler = Scheduler::new();
+        for name in "abcdefgh".chars() {
+            scheduler.spawn(count(name, 100, log.clone()));
+        }
+        scheduler.run_threaded(4);
+        assert!(scheduler.all_done());
+        assert_eq!(log.lock().unwrap().len(), 800);
+        assert_eq!(scheduler.take(7), Some(100));
+    }
+}
@@ -0,0 +1,309 @@
+use super::computer::Computer;
+use super::word::Word;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+//Brute-force search over the values of some memory cells and inputs. Every
+//combination of values runs on a fork of the program until it halts, and the
+//ones whose final machine passes a test are the solutions. Combinations are
+//tried in the order of nested loops over the variables, the last one
+//changing fastest, and solutions always come back in that order whatever the
+//number of threads.
+//
+//Combinations that make the program fail are not solutions. A step limit on
+//the program keeps the ones that never halt from stalling the search.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variable {
+    //Memory cell written before running
+    Cell(usize),
+    //Value queued after the program's own input, in the order declared
+    Input,
+}
+
+#[derive(Clone)]
+pub struct Solution<W: Word> {
+    //Values of the variables in the order they were declared
+    pub values: Vec<W>,
+    //Machine after running with them
+    pub computer: Computer<W>,
+}
+
+//Solutions with their position in the search order
+type Ranked<W> = Vec<(usize, Solution<W>)>;
+
+pub struct Search<W: Word> {
+    program: Computer<W>,
+    variables: Vec<(Variable, Vec<W>)>,
+    threads: usize,
+}
+
+impl<W: Word> Search<W> {
+    pub fn new(program: Computer<W>) -> Self {
+        Search {
+            program,
+            variables: Vec::new(),
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        }
+    }
+
+    pub fn cell<D: IntoIterator<Item = W>>(mut self, address: usize, domain: D) -> Self {
+        self.variables
+            .push((Variable::Cell(address), domain.into_iter().collect()));
+        self
+    }
+
+    pub fn input<D: IntoIterator<Item = W>>(mut self, domain: D) -> Self {
+        self.variables
+            .push((Variable::Input, domain.into_iter().collect()));
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    //Number of combinations to try, usize::MAX when there are more
+    pub fn len(&self) -> usize {
+        self.variables
+            .iter()
+            .fold(1, |total, (_, domain)| total.saturating_mul(domain.len()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    //Tries combinations on every thread, handing each finished one to a
+    //visitor along with its position. Once the visitor returns a position,
+    //combinations after it are no longer started.
+    fn explore<F>(&self, visit: F)
+    where
+        F: Fn(usize, Solution<W>) -> Option<usize> + Sync,
+    {
+        let total = self.len();
+        let next = AtomicUsize::new(0);
+        let cutoff = AtomicUsize::new(usize::MAX);
+        let (variables, next, cutoff, visit) = (&self.variables, &next, &cutoff, &visit);
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                //Machines stay on their thread, each one gets its own copy
+                let program = self.program.clone();
+                scope.spawn(move || loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    if index >= total || index > cutoff.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    let values = combination(variables, index);
+                    if let Some(solution) = try_values(&program, variables, values) {
+                        if let Some(last) = visit(index, solution) {
+                            cutoff.fetch_min(last, Ordering::SeqCst);
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    //Every solution passing the test
+    pub fn solutions<F>(&self, accept: F) -> Vec<Solution<W>>
+    where
+        F: Fn(&Computer<W>) -> bool + Sync,
+    {
+        self.solutions_up_to(usize::MAX, accept)
+    }
+
+    //The first solutions passing the test, stopping as soon as there are
+    //enough of them
+    pub fn solutions_up_to<F>(&self, count: usize, accept: F) -> Vec<Solution<W>>
+    where
+        F: Fn(&Computer<W>) -> bool + Sync,
+    {
+        if count == 0 {
+            return Vec::new();
+        }
+        let found: Mutex<Ranked<W>> = Mutex::new(Vec::new());
+        self.explore(|index, solution| {
+            if !accept(&solution.computer) {
+                return None;
+            }
+            let mut found = found.lock().unwrap();
+            found.push((index, solution));
+            if found.len() < count {
+                return None;
+            }
+            found.sort_by_key(|(index, _)| *index);
+            found.truncate(count);
+            found.last().map(|(index, _)| *index)
+        });
+        let mut found = found.into_inner().unwrap();
+        found.sort_by_key(|(index, _)| *index);
+        found.truncate(count);
+        found.into_iter().map(|(_, solution)| solution).collect()
+    }
+
+    pub fn first<F>(&self, accept: F) -> Option<Solution<W>>
+    where
+        F: Fn(&Computer<W>) -> bool + Sync,
+    {
+        self.solutions_up_to(1, accept).pop()
+    }
+
+    //Every combination sharing the highest score. Combinations scored None
+    //are left out.
+    pub fn maximize<S, F>(&self, score: F) -> Vec<Solution<W>>
+    where
+        S: Ord + Send,
+        F: Fn(&Computer<W>) -> Option<S> + Sync,
+    {
+        let best: Mutex<Option<(S, Ranked<W>)>> = Mutex::new(None);
+        self.explore(|index, solution| {
+            let score = score(&solution.computer)?;
+            let mut best = best.lock().unwrap();
+            match &mut *best {
+                Some((top, ties)) if score == *top => ties.push((index, solution)),
+                Some((top, _)) if score < *top => (),
+                _ => *best = Some((score, vec![(index, solution)])),
+            }
+            None
+        });
+        let mut ties = best
+            .into_inner()
+            .unwrap()
+            .map_or(Vec::new(), |(_, ties)| ties);
+        ties.sort_by_key(|(index, _)| *index);
+        ties.into_iter().map(|(_, solution)| solution).collect()
+    }
+}
+
+//Values of the combination at some position in the search order
+fn combination<W: Word>(variables: &[(Variable, Vec<W>)], mut index: usize) -> Vec<W> {
+    let mut values = Vec::with_capacity(variables.len());
+    for (_, domain) in variables.iter().rev() {
+        values.push(domain[index % domain.len()].clone());
+        index /= domain.len();
+    }
+    values.reverse();
+    values
+}
+
+//Runs the program with a combination, None if it fails
+pub(super) fn try_values<W: Word>(
+    program: &Computer<W>,
+    variables: &[(Variable, Vec<W>)],
+    values: Vec<W>,
+) -> Option<Solution<W>> {
+    let mut computer = program.clone();
+    for ((variable, _), value) in variables.iter().zip(&values) {
+        match variable {
+            Variable::Cell(address) => computer.write(*address, value.clone()).ok()?,
+            Variable::Input => computer.add_input(value.clone()),
+        }
+    }
+    computer.run_to_halt().ok()?;
+    Some(Solution { values, computer })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Reads an input and loops forever
+    fn endless() -> Computer {
+        Computer::new(vec![3, 5, 1105, 1, 2, 0], vec![])
+    }
+
+    //Reads two inputs and multiplies them, halting
+    fn product() -> Computer {
+        Computer::new(
+            vec![3, 11, 3, 12, 2, 11, 12, 13, 4, 13, 99, 0, 0, 0],
+            vec![],
+        )
+    }
+
+    fn values(solutions: &[Solution<i64>]) -> Vec<Vec<i64>> {
+        solutions
+            .iter()
+            .map(|solution| solution.values.clone())
+            .collect()
+    }
+
+    #[test]
+    fn all_solutions_in_order() {
+        for threads in 1..=4 {
+            let search = Search::new(product())
+                .input(1..=12)
+                .input(1..=12)
+                .threads(threads);
+            assert_eq!(search.len(), 144);
+            let solutions = search.solutions(|computer| computer.last_output() == Some(&12));
+            assert_eq!(
+                values(&solutions),
+                vec![
+                    vec![1, 12],
+                    vec![2, 6],
+                    vec![3, 4],
+                    vec![4, 3],
+                    vec![6, 2],
+                    vec![12, 1]
+                ]
+            );
+            assert_eq!(solutions[1].computer.outputs(), &[12]);
+        }
+    }
+
+    #[test]
+    fn searches_stop_early() {
+        let search = Search::new(product()).input(1..=12).input(1..=12);
+        let solutions = search.solutions_up_to(2, |computer| computer.last_output() == Some(&12));
+        assert_eq!(values(&solutions), vec![vec![1, 12], vec![2, 6]]);
+        let first = search.first(|computer| computer.last_output() == Some(&12));
+        assert_eq!(first.map(|solution| solution.values), Some(vec![1, 12]));
+        assert!(search
+            .first(|computer| computer.last_output() == Some(&169))
+            .is_none());
+    }
+
+    #[test]
+    fn cells_and_failures() {
+        //Writing 99 over the multiplication halts early without output and
+        //writing 42 makes it invalid
+        let search = Search::new(product())
+            .cell(4, vec![1, 2, 42, 99])
+            .input(vec![3])
+            .input(vec![5]);
+        let solutions = search.solutions(|computer| !computer.outputs().is_empty());
+        assert_eq!(values(&solutions), vec![vec![1, 3, 5], vec![2, 3, 5]]);
+        assert_eq!(solutions[0].computer.outputs(), &[8]);
+        let mut endless = endless();
+        endless.set_step_limit(1000);
+        let search = Search::new(endless).input(vec![2]).input(vec![3]);
+        assert!(search.solutions(|_| true).is_empty());
+    }
+
+    #[test]
+    fn highest_scores_tie() {
+        let search = Search::new(product()).input(-3..=3).input(-3..=3);
+        let best = search.maximize(|computer| computer.last_output().cloned());
+        assert_eq!(values(&best), vec![vec![-3, -3], vec![3, 3]]);
+        assert!(Search::new(product())
+            .input(Vec::new())
+            .maximize(|computer| computer.last_output().cloned())
+            .is_empty());
+    }
+
+    #[test]
+    fn huge_searches_saturate() {
+        let mut search = Search::new(product());
+        for _ in 0..5 {
+            search = search.input(0..10000);
+        }
+        assert_eq!(search.len(), usize::MAX);
+        assert!(!search.is_empty());
+        let search = search.input(Vec::new());
+        assert_eq!(search.len(), 0);
+        assert!(search.solutions(|_| true).is_empty());
+    }
+}
@@ -0,0 +1,336 @@
+use super::memory::Memory;
+use super::word::Word;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+//Saved state of a computer. Taking and restoring one shares memory with the
+//computer until either of them writes to it.
+//
+//The text form has one field per line:
+//
+//  intcode snapshot
+//  position 4
+//  relative_base 0
+//  halted false
+//  steps 2
+//  memory_limit 16777216
+//  input 5,6
+//  outputs 1
+//  memory 1,2,3,4,99
+//  sparse 100000:7,200000:8
+#[derive(Debug, Clone)]
+pub struct Snapshot<W: Word> {
+    pub(super) memory: Memory<W>,
+    pub(super) position: usize,
+    pub(super) relative_base: i64,
+    pub(super) finished: bool,
+    pub(super) input: Vec<W>,
+    pub(super) outputs: Vec<W>,
+    pub(super) steps: u64,
+}
+
+const HEADER: &str = "intcode snapshot";
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(error) => write!(f, "Cannot access snapshot: {}", error),
+            SnapshotError::Parse { line, message } => {
+                write!(f, "Invalid snapshot at line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(error: io::Error) -> Self {
+        SnapshotError::Io(error)
+    }
+}
+
+impl<W: Word> Snapshot<W> {
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.finished
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn pending_input(&self) -> &[W] {
+        &self.input
+    }
+
+    pub fn outputs(&self) -> &[W] {
+        &self.outputs
+    }
+
+    pub fn read(&self, address: usize) -> W {
+        self.memory.read(address)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SnapshotError> {
+        fs::read_to_string(path)?.parse()
+    }
+}
+
+fn join<W: Word>(values: &[W]) -> String {
+    let values: Vec<_> = values.iter().map(|value| value.to_string()).collect();
+    values.join(",")
+}
+
+impl<W: Word> fmt::Display for Snapshot<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "position {}", self.position)?;
+        writeln!(f, "relative_base {}", self.relative_base)?;
+        writeln!(f, "halted {}", self.finished)?;
+        writeln!(f, "steps {}", self.steps)?;
+        writeln!(f, "memory_limit {}", self.memory.limit())?;
+        writeln!(f, "input {}", join(&self.input))?;
+        writeln!(f, "outputs {}", join(&self.outputs))?;
+        writeln!(f, "memory {}", join(self.memory.dense()))?;
+        let sparse: Vec<_> = self
+            .memory
+            .sparse()
+            .into_iter()
+            .map(|(address, value)| format!("{}:{}", address, value))
+            .collect();
+        writeln!(f, "sparse {}", sparse.join(","))
+    }
+}
+
+fn parse_number<T: FromStr>(text: &str) -> Result<T, String> {
+    text.parse()
+        .map_err(|_| format!("Invalid value: '{}'", text))
+}
+
+fn parse_list<T, F>(text: &str, parse: F) -> Result<Vec<T>, String>
+where
+    F: Fn(&str) -> Result<T, String>,
+{
+    if text.is_empty() {
+        Ok(Vec::new())
+    } else {
+        text.split(',').map(|value| parse(value.trim())).collect()
+    }
+}
+
+fn parse_cell<W: Word>(text: &str) -> Result<(usize, W), String> {
+    let mut parts = text.splitn(2, ':');
+    let address = parse_number(parts.next().unwrap_or(""))?;
+    let value = parts
+        .next()
+        .ok_or_else(|| format!("Invalid cell: '{}'", text))?;
+    Ok((address, parse_number(value)?))
+}
+
+impl<W: Word> FromStr for Snapshot<W> {
+    type Err = SnapshotError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, line)) if line.trim() == HEADER => (),
+            _ => {
+                return Err(SnapshotError::Parse {
+                    line: 1,
+                    message: "Missing snapshot header".to_string(),
+                })
+            }
+        }
+        let mut position = None;
+        let mut relative_base = None;
+        let mut finished = None;
+        let mut steps = None;
+        let mut limit = None;
+        let mut input = None;
+        let mut outputs = None;
+        let mut dense = None;
+        let mut sparse = None;
+        let mut last_line = 1;
+        for (index, line) in lines {
+            last_line = index + 1;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = match line.find(' ') {
+                Some(space) => (&line[..space], line[space + 1..].trim()),
+                None => (line, ""),
+            };
+            let error = |message| SnapshotError::Parse {
+                line: index + 1,
+                message,
+            };
+            match key {
+                "position" => position = Some(parse_number(value).map_err(error)?),
+                "relative_base" => relative_base = Some(parse_number(value).map_err(error)?),
+                "halted" => finished = Some(parse_number(value).map_err(error)?),
+                "steps" => steps = Some(parse_number(value).map_err(error)?),
+                "memory_limit" => limit = Some(parse_number(value).map_err(error)?),
+                "input" => input = Some(parse_list(value, parse_number).map_err(error)?),
+                "outputs" => outputs = Some(parse_list(value, parse_number).map_err(error)?),
+                "memory" => dense = Some(parse_list(value, parse_number).map_err(error)?),
+                "sparse" => sparse = Some(parse_list(value, parse_cell).map_err(error)?),
+                _ => return Err(error(format!("Unknown field: '{}'", key))),
+            }
+        }
+        let missing = |field: &str| SnapshotError::Parse {
+            line: last_line,
+            message: format!("Missing field: '{}'", field),
+        };
+        let memory = Memory::from_parts(
+            dense.ok_or_else(|| missing("memory"))?,
+            sparse.unwrap_or_default(),
+            limit.ok_or_else(|| missing("memory_limit"))?,
+        )
+        .map_err(|error| SnapshotError::Parse {
+            line: last_line,
+            message: error.to_string(),
+        })?;
+        Ok(Snapshot {
+            memory,
+            position: position.ok_or_else(|| missing("position"))?,
+            relative_base: relative_base.ok_or_else(|| missing("relative_base"))?,
+            finished: finished.ok_or_else(|| missing("halted"))?,
+            input: input.unwrap_or_default(),
+            outputs: outputs.unwrap_or_default(),
+            steps: steps.ok_or_else(|| missing("steps"))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{Computer, State};
+    use std::env;
+
+    //Echoes its inputs until it reads a zero, keeping a copy of the last one
+    fn echo() -> Vec<i64> {
+        vec![
+            3, 200_000, 1001, 200_000, 0, 100_000, 4, 200_000, 1005, 200_000, 0, 99,
+        ]
+    }
+
+    fn suspended() -> Computer {
+        let mut computer = Computer::new(echo(), vec![5, 6]);
+        computer.run().unwrap();
+        computer
+    }
+
+    #[test]
+    fn restore_resumes_where_the_snapshot_was_taken() {
+        let mut computer = suspended();
+        let snapshot = computer.snapshot();
+        assert_eq!(snapshot.position(), 8);
+        assert_eq!(snapshot.pending_input(), &[6]);
+        assert_eq!(snapshot.outputs(), &[5]);
+        computer.add_input(0);
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.outputs(), &[5, 6, 0]);
+
+        computer.restore(&snapshot);
+        assert_eq!(computer.position(), 8);
+        assert_eq!(computer.outputs(), &[5]);
+        computer.add_input(7);
+        assert_eq!(computer.run(), Ok(State::Output(6)));
+        assert_eq!(computer.run(), Ok(State::Output(7)));
+    }
+
+    #[test]
+    fn text_form_round_trips() {
+        let snapshot = suspended().snapshot();
+        let text = snapshot.to_string();
+        assert_eq!(
+            text,
+            "intcode snapshot\n\
+             position 8\n\
+             relative_base 0\n\
+             halted false\n\
+             steps 3\n\
+             memory_limit 16777216\n\
+             input 6\n\
+             outputs 5\n\
+             memory 3,200000,1001,200000,0,100000,4,200000,1005,200000,0,99\n\
+             sparse 100000:5,200000:5\n"
+        );
+        let parsed: Snapshot<i64> = text.parse().unwrap();
+        assert_eq!(parsed.to_string(), text);
+        let mut computer = Computer::from_snapshot(parsed);
+        computer.add_input(0);
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.outputs(), &[5, 6, 0]);
+        assert_eq!(computer.read(100_000), 0);
+    }
+
+    #[test]
+    fn saved_to_disk_and_loaded() {
+        let path = env::temp_dir().join(format!("intcode-snapshot-{}.txt", std::process::id()));
+        let snapshot = suspended().snapshot();
+        snapshot.save(&path).unwrap();
+        let loaded: Snapshot<i64> = Snapshot::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.to_string(), snapshot.to_string());
+        assert!(Snapshot::<i64>::load(&path).is_err());
+    }
+
+    #[test]
+    fn invalid_text_reports_its_line() {
+        let error = "state".parse::<Snapshot<i64>>().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid snapshot at line 1: Missing snapshot header"
+        );
+        let error = "intcode snapshot\nposition x"
+            .parse::<Snapshot<i64>>()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid snapshot at line 2: Invalid value: 'x'"
+        );
+        let error = "intcode snapshot\nposition 0\nmemory 99"
+            .parse::<Snapshot<i64>>()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid snapshot at line 3: Missing field: 'memory_limit'"
+        );
+    }
+
+    #[test]
+    fn forks_are_independent() {
+        let computer = suspended();
+        let mut fork = computer.clone();
+        fork.add_input(0);
+        fork.run_to_halt().unwrap();
+        assert!(fork.is_halted());
+        assert!(!computer.is_halted());
+        assert_eq!(computer.read(100_000), 5);
+        assert_eq!(fork.read(100_000), 0);
+    }
+}
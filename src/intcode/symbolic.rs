@@ -0,0 +1,796 @@
+use super::computer::Computer;
+use super::instruction::{Instruction, Operation, ParameterMode};
+use super::memory::Memory;
+use super::search::{try_values, Search, Variable};
+use super::word::Word;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+
+//Symbolic execution of a program over the same variables as a Search. The
+//variables are symbols, arithmetic on them builds expressions and a jump on
+//a symbolic condition forks the path, recording the condition on both sides.
+//
+//Solving for a value observed at the end then works path by path: when the
+//observed expression and the path conditions are linear in the variables,
+//the variable with the most values is solved for algebraically and only the
+//others are enumerated, every candidate being checked against the
+//expressions and every solution run on the interpreter. Anything symbolic
+//execution cannot follow, such as a jump to a symbolic address or a
+//non-linear condition, falls back to running the program for every
+//combination.
+
+//Instructions followed over all the paths before giving up, unless the
+//program has a step limit of its own
+const STEP_LIMIT: u64 = 1_000_000;
+
+//Paths followed before giving up
+const PATH_LIMIT: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr<W> {
+    Const(W),
+    //Value of the variable declared at this index
+    Symbol(usize),
+    Add(Box<Expr<W>>, Box<Expr<W>>),
+    Multiply(Box<Expr<W>>, Box<Expr<W>>),
+    LessThan(Box<Expr<W>>, Box<Expr<W>>),
+    Equals(Box<Expr<W>>, Box<Expr<W>>),
+    //Cell read at an address depending on the variables
+    Load(Box<Expr<W>>),
+}
+
+impl<W: Word> Expr<W> {
+    //Constants are folded, None when folding them overflows
+    fn add(a: Expr<W>, b: Expr<W>) -> Option<Expr<W>> {
+        match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => a.checked_add(&b).map(Expr::Const),
+            (Expr::Const(zero), other) | (other, Expr::Const(zero)) if zero.is_zero() => {
+                Some(other)
+            }
+            (a, b) => Some(Expr::Add(Box::new(a), Box::new(b))),
+        }
+    }
+
+    fn multiply(a: Expr<W>, b: Expr<W>) -> Option<Expr<W>> {
+        match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => a.checked_mul(&b).map(Expr::Const),
+            (Expr::Const(zero), _) | (_, Expr::Const(zero)) if zero.is_zero() => {
+                Some(Expr::Const(zero))
+            }
+            (Expr::Const(one), other) | (other, Expr::Const(one)) if one == W::one() => Some(other),
+            (a, b) => Some(Expr::Multiply(Box::new(a), Box::new(b))),
+        }
+    }
+
+    fn less_than(a: Expr<W>, b: Expr<W>) -> Expr<W> {
+        match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const(truth(a < b)),
+            (a, b) => Expr::LessThan(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn equals(a: Expr<W>, b: Expr<W>) -> Expr<W> {
+        match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const(truth(a == b)),
+            (a, b) => Expr::Equals(Box::new(a), Box::new(b)),
+        }
+    }
+
+    //Value for the given values of the variables, None when the arithmetic
+    //overflows or the expression reads memory
+    pub fn eval(&self, values: &[W]) -> Option<W> {
+        match self {
+            Expr::Const(value) => Some(value.clone()),
+            Expr::Symbol(symbol) => values.get(*symbol).cloned(),
+            Expr::Add(a, b) => a.eval(values)?.checked_add(&b.eval(values)?),
+            Expr::Multiply(a, b) => a.eval(values)?.checked_mul(&b.eval(values)?),
+            Expr::LessThan(a, b) => Some(truth(a.eval(values)? < b.eval(values)?)),
+            Expr::Equals(a, b) => Some(truth(a.eval(values)? == b.eval(values)?)),
+            Expr::Load(_) => None,
+        }
+    }
+
+    //Sum of the variables times constants plus a constant, if it is one
+    fn linear(&self) -> Option<Linear> {
+        match self {
+            Expr::Const(value) => Some(Linear {
+                constant: i128::from(value.to_i64()?),
+                terms: BTreeMap::new(),
+            }),
+            Expr::Symbol(symbol) => {
+                let mut terms = BTreeMap::new();
+                terms.insert(*symbol, 1);
+                Some(Linear { constant: 0, terms })
+            }
+            Expr::Add(a, b) => a.linear()?.plus(&b.linear()?),
+            Expr::Multiply(a, b) => {
+                let (a, b) = (a.linear()?, b.linear()?);
+                if a.terms.is_empty() {
+                    b.times(a.constant)
+                } else if b.terms.is_empty() {
+                    a.times(b.constant)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    //Whether a branch condition is linear or compares linear expressions
+    fn is_linear_condition(&self) -> bool {
+        match self {
+            Expr::LessThan(a, b) | Expr::Equals(a, b) => {
+                a.linear().is_some() && b.linear().is_some()
+            }
+            condition => condition.linear().is_some(),
+        }
+    }
+}
+
+impl<W: Word> fmt::Display for Expr<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Const(value) => write!(f, "{}", value),
+            Expr::Symbol(symbol) => write!(f, "x{}", symbol),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Multiply(a, b) => write!(f, "({} * {})", a, b),
+            Expr::LessThan(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Equals(a, b) => write!(f, "({} == {})", a, b),
+            Expr::Load(address) => write!(f, "[{}]", address),
+        }
+    }
+}
+
+fn truth<W: Word>(condition: bool) -> W {
+    if condition {
+        W::one()
+    } else {
+        W::zero()
+    }
+}
+
+//Constant plus coefficient times variable for every variable present
+#[derive(Debug, Clone)]
+struct Linear {
+    constant: i128,
+    terms: BTreeMap<usize, i128>,
+}
+
+impl Linear {
+    fn plus(&self, other: &Linear) -> Option<Linear> {
+        let mut terms = self.terms.clone();
+        for (symbol, coefficient) in &other.terms {
+            let sum = i128::checked_add(self.coefficient(*symbol), *coefficient)?;
+            if sum == 0 {
+                terms.remove(symbol);
+            } else {
+                terms.insert(*symbol, sum);
+            }
+        }
+        Some(Linear {
+            constant: i128::checked_add(self.constant, other.constant)?,
+            terms,
+        })
+    }
+
+    fn times(&self, factor: i128) -> Option<Linear> {
+        let mut terms = BTreeMap::new();
+        if factor != 0 {
+            for (symbol, coefficient) in &self.terms {
+                terms.insert(*symbol, i128::checked_mul(*coefficient, factor)?);
+            }
+        }
+        Some(Linear {
+            constant: i128::checked_mul(self.constant, factor)?,
+            terms,
+        })
+    }
+
+    fn coefficient(&self, symbol: usize) -> i128 {
+        self.terms.get(&symbol).cloned().unwrap_or(0)
+    }
+}
+
+//One way through the program, from the start to a halt
+#[derive(Debug, Clone)]
+pub struct Path<W: Word> {
+    //Every symbolic branch condition and whether it held on this path
+    pub conditions: Vec<(Expr<W>, bool)>,
+    pub outputs: Vec<Expr<W>>,
+    memory: Memory<W>,
+    written: HashMap<usize, Expr<W>>,
+}
+
+impl<W: Word> Path<W> {
+    //Contents of a cell once the path halted
+    pub fn read(&self, address: usize) -> Expr<W> {
+        match self.written.get(&address) {
+            Some(value) => value.clone(),
+            None => Expr::Const(self.memory.read(address)),
+        }
+    }
+}
+
+//Why a path stopped before halting
+enum Stop {
+    //The program fails there, so no combination following it is a solution
+    Failed,
+    //Symbolic execution cannot follow the program from there
+    Stuck,
+}
+
+enum Step<W: Word> {
+    Next,
+    //The path forked, this is the other side
+    Fork(Box<Machine<W>>),
+    Halted,
+}
+
+#[derive(Clone)]
+struct Machine<W: Word> {
+    path: Path<W>,
+    position: usize,
+    relative_base: i64,
+    input: VecDeque<Expr<W>>,
+    halted: bool,
+}
+
+impl<W: Word> Machine<W> {
+    //Address referenced by the parameter at the given offset, which may
+    //depend on the variables
+    fn address(&self, instruction: Instruction, offset: usize) -> Result<Expr<W>, Stop> {
+        let parameter = self.path.read(self.position + offset);
+        let address = match instruction.mode(offset) {
+            ParameterMode::Position => parameter,
+            //The interpreter adds the base as an i64, failing on overflow
+            ParameterMode::Relative => match parameter {
+                Expr::Const(parameter) => {
+                    let parameter = parameter.to_i64().ok_or(Stop::Failed)?;
+                    let address = self
+                        .relative_base
+                        .checked_add(parameter)
+                        .ok_or(Stop::Failed)?;
+                    Expr::Const(W::from_i64(address).ok_or(Stop::Stuck)?)
+                }
+                parameter => {
+                    let base = W::from_i64(self.relative_base).ok_or(Stop::Stuck)?;
+                    Expr::add(Expr::Const(base), parameter).ok_or(Stop::Failed)?
+                }
+            },
+            ParameterMode::Immediate => return Err(Stop::Failed),
+        };
+        if let Expr::Const(value) = &address {
+            if value.to_i64().map_or(true, |address| address < 0) {
+                return Err(Stop::Failed);
+            }
+        }
+        Ok(address)
+    }
+
+    //Address written by the parameter at the given offset, which has to be
+    //known
+    fn target(&self, instruction: Instruction, offset: usize) -> Result<usize, Stop> {
+        match self.address(instruction, offset)? {
+            Expr::Const(address) => {
+                let address = address.to_i64().ok_or(Stop::Failed)? as usize;
+                if address >= self.path.memory.limit() {
+                    return Err(Stop::Failed);
+                }
+                Ok(address)
+            }
+            _ => Err(Stop::Stuck),
+        }
+    }
+
+    fn operand(&mut self, instruction: Instruction, offset: usize) -> Result<Expr<W>, Stop> {
+        if instruction.mode(offset) == ParameterMode::Immediate {
+            return Ok(self.path.read(self.position + offset));
+        }
+        match self.address(instruction, offset)? {
+            Expr::Const(address) => Ok(self
+                .path
+                .read(address.to_i64().ok_or(Stop::Failed)? as usize)),
+            address => {
+                //The read fails on the combinations making the address negative
+                self.path.conditions.push((
+                    Expr::less_than(address.clone(), Expr::Const(W::zero())),
+                    false,
+                ));
+                Ok(Expr::Load(Box::new(address)))
+            }
+        }
+    }
+
+    //Instruction at the current position, whose opcode has to be known
+    fn instruction(&self) -> Result<Instruction, Stop> {
+        let opcode = match self.path.read(self.position) {
+            Expr::Const(opcode) => opcode.to_i64().ok_or(Stop::Failed)?,
+            _ => return Err(Stop::Stuck),
+        };
+        Instruction::new(opcode).map_err(|_| Stop::Failed)
+    }
+
+    fn write(&mut self, address: usize, value: Expr<W>) {
+        self.path.written.insert(address, value);
+    }
+
+    fn step(&mut self) -> Result<Step<W>, Stop> {
+        if self.halted {
+            return Ok(Step::Halted);
+        }
+        let instruction = self.instruction()?;
+        match instruction.operation {
+            Operation::Add | Operation::Multiply | Operation::LessThan | Operation::Equals => {
+                let a = self.operand(instruction, 1)?;
+                let b = self.operand(instruction, 2)?;
+                let address = self.target(instruction, 3)?;
+                let result = match instruction.operation {
+                    Operation::Add => Expr::add(a, b).ok_or(Stop::Failed)?,
+                    Operation::Multiply => Expr::multiply(a, b).ok_or(Stop::Failed)?,
+                    Operation::LessThan => Expr::less_than(a, b),
+                    _ => Expr::equals(a, b),
+                };
+                self.write(address, result);
+                self.position += 4;
+            }
+            Operation::Save => {
+                let address = self.target(instruction, 1)?;
+                let value = self.input.pop_front().ok_or(Stop::Failed)?;
+                self.write(address, value);
+                self.position += 2;
+            }
+            Operation::Output => {
+                let value = self.operand(instruction, 1)?;
+                self.path.outputs.push(value);
+                self.position += 2;
+            }
+            Operation::JumpIfTrue | Operation::JumpIfFalse => {
+                let condition = self.operand(instruction, 1)?;
+                let jump_if = instruction.operation == Operation::JumpIfTrue;
+                let jumps = match &condition {
+                    Expr::Const(value) => Some(value.is_zero() != jump_if),
+                    _ => None,
+                };
+                if jumps == Some(false) {
+                    self.position += 3;
+                    return Ok(Step::Next);
+                }
+                let target = match self.operand(instruction, 2)? {
+                    Expr::Const(target) => target.to_i64().ok_or(Stop::Failed)?,
+                    _ => return Err(Stop::Stuck),
+                };
+                if jumps == Some(true) {
+                    if target < 0 {
+                        return Err(Stop::Failed);
+                    }
+                    self.position = target as usize;
+                    return Ok(Step::Next);
+                }
+                let mut jumped = self.clone();
+                self.path.conditions.push((condition.clone(), !jump_if));
+                self.position += 3;
+                //Jumping to a negative address fails, leaving only one side
+                if target < 0 {
+                    return Ok(Step::Next);
+                }
+                jumped.path.conditions.push((condition, jump_if));
+                jumped.position = target as usize;
+                return Ok(Step::Fork(Box::new(jumped)));
+            }
+            Operation::AdjustRelativeBase => {
+                let offset = match self.operand(instruction, 1)? {
+                    Expr::Const(offset) => offset.to_i64().ok_or(Stop::Failed)?,
+                    _ => return Err(Stop::Stuck),
+                };
+                self.relative_base = self.relative_base.checked_add(offset).ok_or(Stop::Failed)?;
+                self.position += 2;
+            }
+            Operation::Halt => {
+                self.halted = true;
+                return Ok(Step::Halted);
+            }
+        }
+        Ok(Step::Next)
+    }
+}
+
+//Value looked at once the program halts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Cell(usize),
+    LastOutput,
+}
+
+//How solutions were found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Symbolic,
+    Enumerated,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solved<W> {
+    //Values of the variables for every solution, in the order a Search
+    //would give them
+    pub values: Vec<Vec<W>>,
+    pub method: Method,
+}
+
+//Whether a halted program ends with the target equal to the value
+fn reached<W: Word>(target: Target, value: &W, computer: &Computer<W>) -> bool {
+    match target {
+        Target::Cell(address) => computer.read(address) == *value,
+        Target::LastOutput => computer.last_output() == Some(value),
+    }
+}
+
+pub struct Symbolic<W: Word> {
+    program: Computer<W>,
+    variables: Vec<(Variable, Vec<W>)>,
+}
+
+impl<W: Word> Symbolic<W> {
+    pub fn new(program: Computer<W>) -> Self {
+        Symbolic {
+            program,
+            variables: Vec::new(),
+        }
+    }
+
+    pub fn cell<D: IntoIterator<Item = W>>(mut self, address: usize, domain: D) -> Self {
+        self.variables
+            .push((Variable::Cell(address), domain.into_iter().collect()));
+        self
+    }
+
+    pub fn input<D: IntoIterator<Item = W>>(mut self, domain: D) -> Self {
+        self.variables
+            .push((Variable::Input, domain.into_iter().collect()));
+        self
+    }
+
+    fn start(&self) -> Machine<W> {
+        let snapshot = self.program.snapshot();
+        let mut written = HashMap::new();
+        let mut input: VecDeque<_> = snapshot.input.into_iter().map(Expr::Const).collect();
+        for (symbol, (variable, _)) in self.variables.iter().enumerate() {
+            match variable {
+                Variable::Cell(address) => {
+                    written.insert(*address, Expr::Symbol(symbol));
+                }
+                Variable::Input => input.push_back(Expr::Symbol(symbol)),
+            }
+        }
+        Machine {
+            path: Path {
+                conditions: Vec::new(),
+                outputs: snapshot.outputs.into_iter().map(Expr::Const).collect(),
+                memory: snapshot.memory,
+                written,
+            },
+            position: snapshot.position,
+            relative_base: snapshot.relative_base,
+            input,
+            halted: snapshot.finished,
+        }
+    }
+
+    //Every path reaching a halt. Paths on which the program fails are left
+    //out. None when the program cannot be followed symbolically or takes too
+    //many steps or paths.
+    pub fn paths(&self) -> Option<Vec<Path<W>>> {
+        let limit = self.program.step_limit().map_or(STEP_LIMIT, |limit| {
+            limit.saturating_sub(self.program.steps())
+        });
+        let mut steps = 0;
+        let mut pending = vec![self.start()];
+        let mut paths = Vec::new();
+        while let Some(mut machine) = pending.pop() {
+            loop {
+                if steps >= limit {
+                    return None;
+                }
+                steps += 1;
+                match machine.step() {
+                    Ok(Step::Next) => (),
+                    Ok(Step::Fork(other)) => {
+                        if paths.len() + pending.len() + 2 > PATH_LIMIT {
+                            return None;
+                        }
+                        pending.push(*other);
+                    }
+                    Ok(Step::Halted) => {
+                        paths.push(machine.path);
+                        break;
+                    }
+                    Err(Stop::Failed) => break,
+                    Err(Stop::Stuck) => return None,
+                }
+            }
+        }
+        Some(paths)
+    }
+
+    //Every combination making the program halt with the target equal to the
+    //value
+    pub fn solve(&self, target: Target, value: W) -> Solved<W> {
+        match self.solve_symbolically(target, &value) {
+            Some(values) => Solved {
+                values,
+                method: Method::Symbolic,
+            },
+            None => Solved {
+                values: self.enumerate(target, &value),
+                method: Method::Enumerated,
+            },
+        }
+    }
+
+    fn solve_symbolically(&self, target: Target, value: &W) -> Option<Vec<Vec<W>>> {
+        //Solving is done on i64 values
+        let wanted = value.to_i64()?;
+        let domains = self
+            .variables
+            .iter()
+            .map(|(_, domain)| domain.iter().map(W::to_i64).collect())
+            .collect::<Option<Vec<Vec<i64>>>>()?;
+        let mut found = Vec::new();
+        for path in self.paths()? {
+            let goal = match target {
+                Target::Cell(address) => path.read(address),
+                Target::LastOutput => match path.outputs.last() {
+                    Some(output) => output.clone(),
+                    None => continue,
+                },
+            };
+            let equation = goal.linear()?;
+            if !path
+                .conditions
+                .iter()
+                .all(|(condition, _)| condition.is_linear_condition())
+            {
+                return None;
+            }
+            found.extend(self.solve_path(&path, &goal, &equation, wanted, value, &domains)?);
+        }
+        //Positions in declaration order sort the same way as the search
+        found.sort_by(|(first, _), (second, _)| first.cmp(second));
+        found.dedup_by(|(first, _), (second, _)| first == second);
+        //Every solution is run on the interpreter in case the paths missed
+        //something it does, in which case they cannot be trusted
+        let verified = found.iter().all(|(_, values)| {
+            try_values(&self.program, &self.variables, values.clone())
+                .is_some_and(|solution| reached(target, value, &solution.computer))
+        });
+        if !verified {
+            return None;
+        }
+        Some(found.into_iter().map(|(_, values)| values).collect())
+    }
+
+    //Combinations following the path that make the goal equal to the value,
+    //with the positions of their values in the domains. None when the
+    //arithmetic overflows.
+    fn solve_path(
+        &self,
+        path: &Path<W>,
+        goal: &Expr<W>,
+        equation: &Linear,
+        wanted: i64,
+        value: &W,
+        domains: &[Vec<i64>],
+    ) -> Option<Vec<(Vec<usize>, Vec<W>)>> {
+        //The variable with the most values is solved for, the others are
+        //enumerated
+        let solved = (0..domains.len())
+            .filter(|symbol| equation.coefficient(*symbol) != 0)
+            .max_by_key(|symbol| domains[*symbol].len());
+        let free: Vec<usize> = (0..domains.len())
+            .filter(|symbol| Some(*symbol) != solved)
+            .collect();
+        //Too many combinations to count can never all be tried anyway
+        let count = free.iter().fold(1, |count: usize, symbol| {
+            count.saturating_mul(domains[*symbol].len())
+        });
+        let mut found = Vec::new();
+        for mut index in 0..count {
+            let mut positions = vec![0; domains.len()];
+            for symbol in free.iter().rev() {
+                positions[*symbol] = index % domains[*symbol].len();
+                index /= domains[*symbol].len();
+            }
+            if let Some(solved) = solved {
+                let mut rest = i128::from(wanted).checked_sub(equation.constant)?;
+                for symbol in &free {
+                    let term = equation
+                        .coefficient(*symbol)
+                        .checked_mul(i128::from(domains[*symbol][positions[*symbol]]))?;
+                    rest = rest.checked_sub(term)?;
+                }
+                let coefficient = equation.coefficient(solved);
+                if rest.checked_rem(coefficient)? != 0 {
+                    continue;
+                }
+                let x = rest.checked_div(coefficient)?;
+                match domains[solved]
+                    .iter()
+                    .position(|value| i128::from(*value) == x)
+                {
+                    Some(position) => positions[solved] = position,
+                    None => continue,
+                }
+            }
+            let values: Vec<W> = positions
+                .iter()
+                .zip(&self.variables)
+                .map(|(position, (_, domain))| domain[*position].clone())
+                .collect();
+            let follows = path.conditions.iter().all(|(condition, held)| {
+                condition.eval(&values).map(|value| !value.is_zero()) == Some(*held)
+            });
+            if follows && goal.eval(&values).as_ref() == Some(value) {
+                found.push((positions, values));
+            }
+        }
+        Some(found)
+    }
+
+    fn enumerate(&self, target: Target, value: &W) -> Vec<Vec<W>> {
+        let mut search = Search::new(self.program.clone());
+        for (variable, domain) in &self.variables {
+            search = match variable {
+                Variable::Cell(address) => search.cell(*address, domain.clone()),
+                Variable::Input => search.input(domain.clone()),
+            };
+        }
+        search
+            .solutions(|computer| reached(target, value, computer))
+            .into_iter()
+            .map(|solution| solution.values)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::assembler::assembled;
+
+    #[test]
+    fn arithmetic_builds_expressions() {
+        //[20] = 3 * x0 + x1 + 4, then outputs whether x1 is below it
+        let symbolic = Symbolic::new(assembled(
+            "mul 3, [20], [20]
+             add [20], [21], [20]
+             add [20], 4, [20]
+             lt [21], [20], [22]
+             out [22]
+             hlt",
+        ))
+        .cell(20, 0..10)
+        .cell(21, 0..10);
+        let paths = symbolic.paths().unwrap();
+        assert_eq!(paths.len(), 1);
+        let result = paths[0].read(20);
+        assert_eq!(result.to_string(), "(((3 * x0) + x1) + 4)");
+        assert_eq!(result.eval(&[2, 5]), Some(15));
+        assert_eq!(
+            paths[0].outputs[0].to_string(),
+            "(x1 < (((3 * x0) + x1) + 4))"
+        );
+        assert_eq!(paths[0].read(5), Expr::Const(20));
+        let solved = symbolic.solve(Target::Cell(20), 15);
+        assert_eq!(solved.method, Method::Symbolic);
+        assert_eq!(solved.values, vec![vec![1, 8], vec![2, 5], vec![3, 2]]);
+    }
+
+    #[test]
+    fn branches_fork_paths() {
+        //Outputs the input doubled when it is below 5 and plus 100 otherwise
+        let symbolic = Symbolic::new(assembled(
+            "in [40]
+             lt [40], 5, [41]
+             jf [41], large
+             mul [40], 2, [42]
+             out [42]
+             hlt
+             large: add [40], 100, [42]
+             out [42]
+             hlt",
+        ))
+        .input(0..200);
+        let paths = symbolic.paths().unwrap();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].conditions, vec![(paths[0].read(41), true)]);
+        assert_eq!(paths[1].conditions, vec![(paths[0].read(41), false)]);
+        let solved = symbolic.solve(Target::LastOutput, 8);
+        assert_eq!(solved.method, Method::Symbolic);
+        assert_eq!(solved.values, vec![vec![4]]);
+        assert!(symbolic.solve(Target::LastOutput, 104).values.is_empty());
+        assert_eq!(
+            symbolic.solve(Target::LastOutput, 105).values,
+            vec![vec![5]]
+        );
+    }
+
+    #[test]
+    fn relative_base_overflow_fails_the_path() {
+        //Outputs 1 for an input of 0 and overflows the relative base otherwise
+        let symbolic = Symbolic::new(assembled(
+            "in [20]
+             jt [20], big
+             out 1
+             hlt
+             big: arb 9223372036854775807
+             arb 1
+             out 2
+             hlt",
+        ))
+        .input(0..3);
+        assert_eq!(symbolic.paths().unwrap().len(), 1);
+        let solved = symbolic.solve(Target::LastOutput, 1);
+        assert_eq!(solved.method, Method::Symbolic);
+        assert_eq!(solved.values, vec![vec![0]]);
+        assert!(symbolic.solve(Target::LastOutput, 2).values.is_empty());
+    }
+
+    #[test]
+    fn huge_domains_do_not_overflow() {
+        //Outputs the sum of seven inputs, the last of which has no values
+        let mut symbolic = Symbolic::new(assembled(
+            "in [100]
+             in [101]
+             in [102]
+             in [103]
+             in [104]
+             in [105]
+             in [106]
+             add [100], [101], [110]
+             add [110], [102], [110]
+             add [110], [103], [110]
+             add [110], [104], [110]
+             add [110], [105], [110]
+             add [110], [106], [110]
+             out [110]
+             hlt",
+        ));
+        for _ in 0..6 {
+            symbolic = symbolic.input(0..10000);
+        }
+        let solved = symbolic.input(Vec::new()).solve(Target::LastOutput, 5);
+        assert_eq!(solved.method, Method::Symbolic);
+        assert!(solved.values.is_empty());
+    }
+
+    #[test]
+    fn non_linear_programs_are_enumerated() {
+        let symbolic = Symbolic::new(assembled(
+            "in [20]
+             in [21]
+             mul [20], [21], [22]
+             out [22]
+             hlt",
+        ))
+        .input(1..=6)
+        .input(1..=6);
+        let solved = symbolic.solve(Target::LastOutput, 6);
+        assert_eq!(solved.method, Method::Enumerated);
+        assert_eq!(
+            solved.values,
+            vec![vec![1, 6], vec![2, 3], vec![3, 2], vec![6, 1]]
+        );
+    }
+
+    #[test]
+    fn symbolic_jumps_are_enumerated() {
+        //Jumps to the address read, which only halts for 5 and 6
+        let mut computer = assembled("in [20]\njt 1, [20]\nhlt\nhlt");
+        computer.set_step_limit(1000);
+        let symbolic = Symbolic::new(computer).input(0..10);
+        assert!(symbolic.paths().is_none());
+        let solved = symbolic.solve(Target::Cell(20), 6);
+        assert_eq!(solved.method, Method::Enumerated);
+        assert_eq!(solved.values, vec![vec![6]]);
+    }
+}
@@ -0,0 +1,245 @@
+use super::computer::Computer;
+use super::error::IntcodeError;
+use super::network::{Merge, Network};
+use super::word::Word;
+
+//Builder for graphs of machines such as the day7 amplifiers. Every node is a
+//fork of some machine given its settings as first inputs; nodes are wired in
+//series, rings, fan-outs and fan-ins, and running the graph gives back the
+//last signal sent by each of the nodes marked as outputs. Wiring a node
+//that was never added is reported when the graph is run.
+pub struct Topology<W: Word> {
+    network: Network<W>,
+    outputs: Vec<usize>,
+    //First wiring error
+    error: Option<IntcodeError>,
+}
+
+impl<W: Word> Default for Topology<W> {
+    fn default() -> Self {
+        Topology::new()
+    }
+}
+
+impl<W: Word> Topology<W> {
+    pub fn new() -> Self {
+        Topology {
+            network: Network::new(),
+            outputs: Vec::new(),
+            error: None,
+        }
+    }
+
+    fn record(&mut self, result: Result<(), IntcodeError>) {
+        if let Err(error) = result {
+            self.error.get_or_insert(error);
+        }
+    }
+
+    //Adds a fork of a machine reading the settings before any signal
+    pub fn node(&mut self, computer: &Computer<W>, settings: &[W]) -> usize {
+        let node = self.network.add_machine(computer.clone());
+        for setting in settings {
+            self.network
+                .send(node, setting.clone())
+                .expect("The node was just added");
+        }
+        node
+    }
+
+    //Adds one fork of a machine per phase setting
+    pub fn nodes(&mut self, computer: &Computer<W>, phases: &[W]) -> Vec<usize> {
+        phases
+            .iter()
+            .map(|phase| self.node(computer, std::slice::from_ref(phase)))
+            .collect()
+    }
+
+    pub fn connect(&mut self, from: usize, to: usize) -> &mut Self {
+        let result = self.network.connect(from, to);
+        self.record(result);
+        self
+    }
+
+    //Every node feeds the next one
+    pub fn series(&mut self, nodes: &[usize]) -> &mut Self {
+        for pair in nodes.windows(2) {
+            self.connect(pair[0], pair[1]);
+        }
+        self
+    }
+
+    //A series whose last node feeds the first one
+    pub fn ring(&mut self, nodes: &[usize]) -> &mut Self {
+        self.series(nodes);
+        if let (Some(first), Some(last)) = (nodes.first(), nodes.last()) {
+            self.connect(*last, *first);
+        }
+        self
+    }
+
+    //One node feeds all the others
+    pub fn fan_out(&mut self, from: usize, to: &[usize]) -> &mut Self {
+        for node in to {
+            self.connect(from, *node);
+        }
+        self
+    }
+
+    //All the nodes feed one, which combines their signals as the policy says
+    pub fn fan_in(&mut self, from: &[usize], to: usize, merge: Merge) -> &mut Self {
+        for node in from {
+            self.connect(*node, to);
+        }
+        let result = self.network.set_merge(to, merge);
+        self.record(result);
+        self
+    }
+
+    //Signal sent to a node from outside, after its settings
+    pub fn send(&mut self, node: usize, signal: W) -> &mut Self {
+        let result = self.network.send(node, signal);
+        self.record(result);
+        self
+    }
+
+    //Marks a node whose last signal run gives back
+    pub fn output(&mut self, node: usize) -> &mut Self {
+        if node >= self.network.len() {
+            self.record(Err(IntcodeError::UnknownMachine { index: node }));
+        }
+        self.outputs.push(node);
+        self
+    }
+
+    //Runs until no node can make progress. Returns the last signal of every
+    //output node in the order they were marked, None for those that sent none.
+    pub fn run(self) -> Result<Vec<Option<W>>, IntcodeError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        let outputs = self.outputs;
+        Ok(Topology::signals(self.network.run()?, &outputs))
+    }
+
+    //Same as run, with the nodes spread over several threads
+    pub fn run_threaded(self, threads: usize) -> Result<Vec<Option<W>>, IntcodeError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        let outputs = self.outputs;
+        Ok(Topology::signals(
+            self.network.run_threaded(threads)?,
+            &outputs,
+        ))
+    }
+
+    fn signals(machines: Vec<Computer<W>>, outputs: &[usize]) -> Vec<Option<W>> {
+        outputs
+            .iter()
+            .map(|node| machines[*node].last_output().cloned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::assembler::assembled;
+    use crate::intcode::loader::load_program;
+
+    fn amplifier(name: &str) -> Computer {
+        let image = load_program(format!("input/{}.txt", name)).unwrap();
+        Computer::new(image, vec![])
+    }
+
+    #[test]
+    fn amplifiers_in_series() {
+        let mut topology = Topology::new();
+        let amplifiers = topology.nodes(&amplifier("day7_example1"), &[4, 3, 2, 1, 0]);
+        topology
+            .series(&amplifiers)
+            .send(amplifiers[0], 0)
+            .output(amplifiers[4])
+            .output(amplifiers[0]);
+        assert_eq!(topology.run(), Ok(vec![Some(43210), Some(4)]));
+    }
+
+    #[test]
+    fn amplifiers_in_a_ring() {
+        let build = || {
+            let mut topology = Topology::new();
+            let amplifiers = topology.nodes(&amplifier("day7_example4"), &[9, 8, 7, 6, 5]);
+            topology
+                .ring(&amplifiers)
+                .send(amplifiers[0], 0)
+                .output(amplifiers[4]);
+            topology
+        };
+        assert_eq!(build().run(), Ok(vec![Some(139629729)]));
+        assert_eq!(build().run_threaded(2), Ok(vec![Some(139629729)]));
+    }
+
+    #[test]
+    fn fan_out_then_fan_in() {
+        //Checked against the same amplifiers run one after the other
+        let computer = amplifier("day7");
+        let mut topology = Topology::new();
+        let source = topology.node(&computer, &[0]);
+        let branches = topology.nodes(&computer, &[1, 2, 3]);
+        let sink = topology.node(&computer, &[4]);
+        let silent = topology.node(&computer, &[0]);
+        topology
+            .fan_out(source, &branches)
+            .fan_in(&branches, sink, Merge::Max)
+            .send(source, 1)
+            .output(sink)
+            .output(silent);
+        let signals = topology.run().unwrap();
+        let mut expected = computer.clone();
+        expected.add_input(0);
+        expected.add_input(1);
+        expected.run_to_halt().unwrap();
+        let through_source = *expected.last_output().unwrap();
+        let best = [1, 2, 3]
+            .iter()
+            .map(|phase| {
+                let mut branch = computer.clone();
+                branch.add_input(*phase);
+                branch.add_input(through_source);
+                branch.run_to_halt().unwrap();
+                *branch.last_output().unwrap()
+            })
+            .max()
+            .unwrap();
+        let mut sink = computer.clone();
+        sink.add_input(4);
+        sink.add_input(best);
+        sink.run_to_halt().unwrap();
+        assert_eq!(signals, vec![sink.last_output().cloned(), None]);
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let mut topology = Topology::new();
+        let node = topology.node(&assembled("in [rb-1]"), &[]);
+        topology.send(node, 5).output(node);
+        assert_eq!(
+            topology.run(),
+            Err(IntcodeError::NegativeAddress {
+                address: 0,
+                target: -1
+            })
+        );
+        let mut topology = Topology::new();
+        let nodes = topology.nodes(&assembled("hlt"), &[1, 2]);
+        topology
+            .series(&nodes)
+            .connect(nodes[1], 7)
+            .output(nodes[1]);
+        assert_eq!(
+            topology.run_threaded(2),
+            Err(IntcodeError::UnknownMachine { index: 7 })
+        );
+    }
+}
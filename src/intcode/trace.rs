@@ -0,0 +1,336 @@
+use super::instruction::{Instruction, Operation};
+use super::word::Word;
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+//Everything an executed instruction did
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEvent<W> {
+    //Instructions executed before this one
+    pub step: u64,
+    pub address: usize,
+    pub instruction: Instruction,
+    //Values of the parameters the instruction reads, modes already applied
+    pub operands: Vec<W>,
+    //Memory cell written and the value stored there
+    pub write: Option<(usize, W)>,
+    pub relative_base: i64,
+}
+
+impl<W: Word> TraceEvent<W> {
+    //Single line JSON object describing the event
+    pub fn to_json(&self) -> String {
+        let modes: Vec<_> = (1..=self.instruction.operation.parameters())
+            .map(|offset| self.instruction.mode(offset).digit().to_string())
+            .collect();
+        let operands: Vec<_> = self
+            .operands
+            .iter()
+            .map(|value| value.to_string())
+            .collect();
+        let write = match &self.write {
+            Some((address, value)) => format!("{{\"address\":{},\"value\":{}}}", address, value),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"step\":{},\"pc\":{},\"op\":\"{}\",\"modes\":[{}],\"operands\":[{}],\"write\":{},\"rb\":{}}}",
+            self.step,
+            self.address,
+            self.instruction.operation.mnemonic(),
+            modes.join(","),
+            operands.join(","),
+            write,
+            self.relative_base
+        )
+    }
+}
+
+//Lets a tracer be looked at again once it has been handed to a computer
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+//Receives every instruction a computer executes
+pub trait Tracer<W>: AsAny + Send + 'static {
+    fn record(&mut self, event: &TraceEvent<W>);
+
+    //Hands on whatever is still buffered
+    fn flush(&mut self) {}
+}
+
+//Keeps the most recent events, dropping the oldest ones
+#[derive(Debug, Clone)]
+pub struct RingTracer<W> {
+    capacity: usize,
+    events: VecDeque<TraceEvent<W>>,
+}
+
+impl<W> RingTracer<W> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            events: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    //Recorded events, oldest first
+    pub fn events(&self) -> impl Iterator<Item = &TraceEvent<W>> {
+        self.events.iter()
+    }
+}
+
+impl<W: Word> Tracer<W> for RingTracer<W> {
+    fn record(&mut self, event: &TraceEvent<W>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(event.clone());
+    }
+}
+
+//Writes one JSON object per line. The first write error stops the trace and
+//is kept for the caller.
+pub struct JsonLinesTracer<T: Write> {
+    writer: T,
+    error: Option<io::Error>,
+}
+
+impl<T: Write> JsonLinesTracer<T> {
+    pub fn new(writer: T) -> Self {
+        Self {
+            writer,
+            error: None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.writer
+    }
+}
+
+impl JsonLinesTracer<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Word, T: Write + Send + 'static> Tracer<W> for JsonLinesTracer<T> {
+    fn record(&mut self, event: &TraceEvent<W>) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = writeln!(self.writer, "{}", event.to_json()).and_then(|_| {
+            if event.instruction.operation == Operation::Halt {
+                self.writer.flush()
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(error) = result {
+            self.error = Some(error);
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(error) = self.writer.flush() {
+            self.error = Some(error);
+        }
+    }
+}
+
+//Counts executed instructions per operation and per address
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    total: u64,
+    operations: HashMap<Operation, u64>,
+    addresses: HashMap<usize, u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn count(&self, operation: Operation) -> u64 {
+        self.operations.get(&operation).cloned().unwrap_or(0)
+    }
+
+    //Operations by number of executions, most frequent first
+    pub fn operations(&self) -> Vec<(Operation, u64)> {
+        let mut operations: Vec<_> = self
+            .operations
+            .iter()
+            .map(|(operation, count)| (*operation, *count))
+            .collect();
+        operations.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.opcode().cmp(&b.0.opcode())));
+        operations
+    }
+
+    //Addresses executed most often, at most `count` of them
+    pub fn hot_addresses(&self, count: usize) -> Vec<(usize, u64)> {
+        let mut addresses: Vec<_> = self
+            .addresses
+            .iter()
+            .map(|(address, hits)| (*address, *hits))
+            .collect();
+        addresses.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        addresses.truncate(count);
+        addresses
+    }
+
+    //Human readable report with the given number of hot addresses
+    pub fn summary(&self, hot: usize) -> String {
+        let mut summary = format!("{} instructions\n", self.total);
+        for (operation, count) in self.operations() {
+            summary.push_str(&format!(
+                "{:<4}{:>12}  {:>5.1}%\n",
+                operation.mnemonic(),
+                count,
+                100.0 * count as f64 / self.total as f64
+            ));
+        }
+        summary.push_str("hot addresses:\n");
+        for (address, hits) in self.hot_addresses(hot) {
+            summary.push_str(&format!("{:>6}{:>12}\n", address, hits));
+        }
+        summary
+    }
+}
+
+impl<W: Word> Tracer<W> for Profiler {
+    fn record(&mut self, event: &TraceEvent<W>) {
+        self.total += 1;
+        *self
+            .operations
+            .entry(event.instruction.operation)
+            .or_insert(0) += 1;
+        *self.addresses.entry(event.address).or_insert(0) += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::Computer;
+
+    //Counts down from 3, printing every value
+    fn countdown() -> Vec<i64> {
+        vec![4, 12, 1001, 12, -1, 12, 1005, 12, 0, 99, 0, 0, 3]
+    }
+
+    #[test]
+    fn ring_keeps_the_latest_events() {
+        let mut computer = Computer::new(countdown(), vec![]);
+        computer.set_tracer(RingTracer::new(2));
+        computer.run_to_halt().unwrap();
+        let ring = computer.tracer::<RingTracer<i64>>().unwrap();
+        let events: Vec<_> = ring.events().collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].step, 8);
+        assert_eq!(events[0].instruction.operation, Operation::JumpIfTrue);
+        assert_eq!(events[0].operands, vec![0, 0]);
+        assert_eq!(events[1].address, 9);
+        assert_eq!(events[1].instruction.operation, Operation::Halt);
+    }
+
+    #[test]
+    fn events_record_operands_and_writes() {
+        let mut computer = Computer::new(vec![109, 3, 21101, 2, 5, 4, 99], vec![]);
+        computer.set_tracer(RingTracer::new(10));
+        computer.run_to_halt().unwrap();
+        let ring = computer.tracer::<RingTracer<i64>>().unwrap();
+        let events: Vec<_> = ring.events().collect();
+        assert_eq!(events[0].operands, vec![3]);
+        assert_eq!(events[0].write, None);
+        assert_eq!(events[1].operands, vec![2, 5]);
+        assert_eq!(events[1].write, Some((7, 7)));
+        assert_eq!(events[1].relative_base, 3);
+        assert_eq!(
+            events[1].to_json(),
+            "{\"step\":1,\"pc\":2,\"op\":\"add\",\"modes\":[1,1,2],\"operands\":[2,5],\
+             \"write\":{\"address\":7,\"value\":7},\"rb\":3}"
+        );
+    }
+
+    #[test]
+    fn json_lines_are_written_per_instruction() {
+        let mut computer = Computer::new(vec![3, 0, 4, 0, 99], vec![8]);
+        computer.set_tracer(JsonLinesTracer::new(Vec::new()));
+        computer.run_to_halt().unwrap();
+        let tracer = computer.tracer::<JsonLinesTracer<Vec<u8>>>().unwrap();
+        assert!(tracer.error().is_none());
+        let text = String::from_utf8(tracer.writer.clone()).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            "{\"step\":0,\"pc\":0,\"op\":\"in\",\"modes\":[0],\"operands\":[],\
+             \"write\":{\"address\":0,\"value\":8},\"rb\":0}"
+        );
+        assert!(lines[1].contains("\"op\":\"out\",\"modes\":[0],\"operands\":[8]"));
+    }
+
+    #[test]
+    fn json_lines_are_flushed_when_runs_fail() {
+        let mut computer = Computer::new(countdown(), vec![]);
+        computer.set_step_limit(2);
+        computer.set_tracer(JsonLinesTracer::new(BufWriter::new(Vec::new())));
+        assert!(computer.run_to_halt().is_err());
+        computer.flush_tracer();
+        let tracer = computer
+            .tracer::<JsonLinesTracer<BufWriter<Vec<u8>>>>()
+            .unwrap();
+        assert!(tracer.error().is_none());
+        assert_eq!(
+            tracer.writer.get_ref().split(|byte| *byte == b'\n').count(),
+            3
+        );
+    }
+
+    #[test]
+    fn profiler_counts_operations_and_addresses() {
+        let mut computer = Computer::new(countdown(), vec![]);
+        computer.set_tracer(Profiler::new());
+        computer.run_to_halt().unwrap();
+        let profiler = computer.tracer::<Profiler>().unwrap();
+        assert_eq!(profiler.total(), computer.steps());
+        assert_eq!(profiler.count(Operation::Output), 3);
+        assert_eq!(profiler.count(Operation::Halt), 1);
+        assert_eq!(profiler.hot_addresses(1), vec![(0, 3)]);
+        let summary = profiler.summary(2);
+        assert!(summary.starts_with("10 instructions\n"));
+        assert!(summary.contains("hot addresses:\n     0           3\n     2           3\n"));
+    }
+
+    #[test]
+    fn tracer_of_another_type_is_not_returned() {
+        let mut computer = Computer::new(countdown(), vec![]);
+        computer.set_tracer(Profiler::new());
+        assert!(computer.tracer::<RingTracer<i64>>().is_none());
+        assert!(computer.take_tracer().is_some());
+        assert!(computer.tracer::<Profiler>().is_none());
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+
+//Value stored in every memory cell of the machine. Arithmetic is checked so
+//programs overflowing the chosen word size are detected instead of wrapped.
+pub trait Word:
+    Clone + Debug + Display + FromStr + PartialEq + PartialOrd + Send + Sync + 'static
+{
+    //None when the value does not fit in the word
+    fn from_i64(value: i64) -> Option<Self>;
+
+    //None when the value does not fit in an i64
+    fn to_i64(&self) -> Option<i64>;
+
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+
+    fn zero() -> Self {
+        Self::from_i64(0).unwrap()
+    }
+
+    fn one() -> Self {
+        Self::from_i64(1).unwrap()
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == Self::zero()
+    }
+}
+
+macro_rules! primitive_word {
+    ($($t:ty),*) => {
+        $(
+            impl Word for $t {
+                fn from_i64(value: i64) -> Option<Self> {
+                    use std::convert::TryFrom;
+                    <$t>::try_from(value).ok()
+                }
+
+                fn to_i64(&self) -> Option<i64> {
+                    use std::convert::TryFrom;
+                    i64::try_from(*self).ok()
+                }
+
+                fn checked_add(&self, other: &Self) -> Option<Self> {
+                    <$t>::checked_add(*self, *other)
+                }
+
+                fn checked_mul(&self, other: &Self) -> Option<Self> {
+                    <$t>::checked_mul(*self, *other)
+                }
+            }
+        )*
+    };
+}
+
+primitive_word!(i32, i64, i128);
+
+#[cfg(feature = "bigint")]
+impl Word for num_bigint::BigInt {
+    fn from_i64(value: i64) -> Option<Self> {
+        Some(num_bigint::BigInt::from(value))
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        num_traits::ToPrimitive::to_i64(self)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primitive_overflow_is_detected() {
+        assert_eq!(Word::checked_mul(&i32::MAX, &2), None);
+        assert_eq!(Word::checked_add(&i64::MAX, &1), None);
+        assert_eq!(
+            Word::checked_mul(&34915192i64, &34915192),
+            Some(1219070632396864)
+        );
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(<i128 as Word>::from_i64(-7).unwrap().to_i64(), Some(-7));
+        assert_eq!(<i32 as Word>::from_i64(i64::MAX), None);
+        assert_eq!(<i32 as Word>::from_i64(-1), Some(-1));
+        assert_eq!((i128::MAX).to_i64(), None);
+        assert!(<i32 as Word>::zero().is_zero());
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn bigint_does_not_overflow() {
+        let big = num_bigint::BigInt::from_i64(i64::MAX).unwrap();
+        let product = big.checked_mul(&big).unwrap();
+        assert_eq!(product.to_i64(), None);
+        assert_eq!(
+            product.to_string(),
+            "85070591730234615847396907784232501249"
+        );
+    }
+}
@@ -6,6 +6,7 @@ mod day5;
 mod day6;
 mod day7;
 mod day8;
+pub mod intcode;
 
 #[cfg(test)]
 mod tests {